@@ -0,0 +1,74 @@
+use crate::board::{Board, Piece};
+
+// Something the player to move can do: place a stone, or answer one of the
+// choices of a swap opening
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Action {
+    Place(usize, usize),
+    // Take the other color
+    Swap,
+    // Keep the current color
+    Stay,
+    // Swap2: place two more stones, one of each color, and let the opponent choose
+    PlaceTwo,
+    // D-Pente: play the given side from now on
+    ChooseColor(Piece),
+}
+
+// Actions that aren't board points, in the order they follow the points in a
+// flat action space
+pub const CHOICES: [Action; 5] = [
+    Action::Swap,
+    Action::Stay,
+    Action::PlaceTwo,
+    Action::ChooseColor(Piece::Black),
+    Action::ChooseColor(Piece::White),
+];
+
+impl Action {
+    // Number of actions in the flat action space of the given board
+    pub fn space_size(board: &Board) -> usize {
+        board.rows * board.cols + CHOICES.len()
+    }
+
+    // Position in the flat action space: board points in row-major order, then the choices
+    pub fn index(&self, board: &Board) -> usize {
+        match *self {
+            Action::Place(x, y) => x * board.cols + y,
+            choice => board.rows * board.cols + CHOICES.iter().position(|&c| c == choice).unwrap(),
+        }
+    }
+
+    pub fn from_index(index: usize, board: &Board) -> Option<Action> {
+        let points = board.rows * board.cols;
+        if index < points {
+            Some(Action::Place(index / board.cols, index % board.cols))
+        } else {
+            CHOICES.get(index - points).copied()
+        }
+    }
+}
+
+impl From<(usize, usize)> for Action {
+    fn from((x, y): (usize, usize)) -> Action {
+        Action::Place(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_index_round_trip() {
+        let board = Board::rectangular(5, 3);
+        for index in 0..Action::space_size(&board) {
+            let action = Action::from_index(index, &board).unwrap();
+            assert_eq!(action.index(&board), index);
+        }
+        assert_eq!(Action::from_index(7, &board), Some(Action::Place(2, 1)));
+        assert_eq!(Action::from_index(16, &board), Some(Action::Stay));
+        assert_eq!(Action::from_index(19, &board), Some(Action::ChooseColor(Piece::White)));
+        assert_eq!(Action::from_index(Action::space_size(&board), &board), None);
+    }
+}
@@ -1,7 +1,9 @@
 use std::fmt;
-use ndarray::{array, Array2, Dimension};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not, RangeInclusive, Shr};
 
-#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+use crate::zobrist;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
 pub enum Piece {
     Empty,
     Black,
@@ -22,43 +24,634 @@ impl fmt::Display for Piece {
     }
 }
 
-#[derive(Clone)]
+// Largest supported number of rows or columns
+pub const MAX_SIZE: usize = 128;
+
+// Words of a padded 19x19 board, the largest kept inline
+const INLINE_WORDS: usize = (19 * (19 + 1usize)).div_ceil(64);
+
+// Bitset sized to a padded board. Each row is stored with one padding bit after
+// the last column so horizontal and diagonal shifts never wrap into the next row.
+// Boards up to 19x19 keep their bits inline, so copying them doesn't allocate.
+// All bitsets of a board have the same length and storage.
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+enum Bits {
+    Inline([u64; INLINE_WORDS]),
+    Heap(Vec<u64>),
+}
+
+impl Bits {
+    fn new(len: usize) -> Bits {
+        let words = len.div_ceil(64);
+        if words <= INLINE_WORDS {
+            Bits::Inline([0; INLINE_WORDS])
+        } else {
+            Bits::Heap(vec![0; words])
+        }
+    }
+
+    // An empty bitset of the same length
+    fn zeroed(&self) -> Bits {
+        match self {
+            Bits::Inline(_) => Bits::Inline([0; INLINE_WORDS]),
+            Bits::Heap(words) => Bits::Heap(vec![0; words.len()]),
+        }
+    }
+
+    fn words(&self) -> &[u64] {
+        match self {
+            Bits::Inline(words) => words,
+            Bits::Heap(words) => words,
+        }
+    }
+
+    fn words_mut(&mut self) -> &mut [u64] {
+        match self {
+            Bits::Inline(words) => words,
+            Bits::Heap(words) => words,
+        }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words().get(i / 64).is_some_and(|w| w & (1 << (i % 64)) != 0)
+    }
+
+    fn set(&mut self, i: usize) {
+        self.words_mut()[i / 64] |= 1 << (i % 64);
+    }
+
+    fn clear(&mut self, i: usize) {
+        self.words_mut()[i / 64] &= !(1 << (i % 64));
+    }
+
+    fn is_zero(&self) -> bool {
+        self.words().iter().all(|&w| w == 0)
+    }
+
+    fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words().iter().enumerate().flat_map(|(w, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+}
+
+impl BitAnd<&Bits> for Bits {
+    type Output = Bits;
+    fn bitand(mut self, rhs: &Bits) -> Bits {
+        self &= rhs;
+        self
+    }
+}
+
+impl BitAndAssign<&Bits> for Bits {
+    fn bitand_assign(&mut self, rhs: &Bits) {
+        for (a, b) in self.words_mut().iter_mut().zip(rhs.words()) {
+            *a &= b;
+        }
+    }
+}
+
+impl BitOr<&Bits> for Bits {
+    type Output = Bits;
+    fn bitor(mut self, rhs: &Bits) -> Bits {
+        self |= rhs;
+        self
+    }
+}
+
+impl BitOrAssign<&Bits> for Bits {
+    fn bitor_assign(&mut self, rhs: &Bits) {
+        for (a, b) in self.words_mut().iter_mut().zip(rhs.words()) {
+            *a |= b;
+        }
+    }
+}
+
+impl Not for Bits {
+    type Output = Bits;
+    fn not(mut self) -> Bits {
+        for w in self.words_mut().iter_mut() {
+            *w = !*w;
+        }
+        self
+    }
+}
+
+// Shift toward bit 0, so bit i of (b >> n) is bit i + n of b
+impl Shr<usize> for &Bits {
+    type Output = Bits;
+    fn shr(self, n: usize) -> Bits {
+        let mut out = self.zeroed();
+        let (src, dst) = (self.words(), out.words_mut());
+        let (words, bits) = (n / 64, n % 64);
+        for i in 0..src.len().saturating_sub(words) {
+            let lo = src[i + words] >> bits;
+            let hi = match (bits, src.get(i + words + 1)) {
+                (0, _) | (_, None) => 0,
+                (_, Some(next)) => next << (64 - bits),
+            };
+            dst[i] = lo | hi;
+        }
+        out
+    }
+}
+
+// Index of the per-color bitset for a piece
+fn slot(piece: Piece) -> Option<usize> {
+    match piece {
+        Piece::Empty => None,
+        Piece::Black => Some(0),
+        Piece::White => Some(1),
+        Piece::Red   => Some(2),
+        Piece::Green => Some(3),
+    }
+}
+
+// Stones removed from the board, each with the color it had
+pub type CapturedStones = Vec<((usize, usize), Piece)>;
+
+const PIECES: [Piece; 4] = [Piece::Black, Piece::White, Piece::Red, Piece::Green];
+
+// One direction of each of the four lines through a point
+const LINES: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+// How the edges of the board connect
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Topology {
+    // Lines end at the edges
+    Flat,
+    // Lines leaving one edge continue from the opposite edge
+    Torus,
+}
+
+#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Board {
-    pub size: usize,
-    pub grid: Array2<Piece>,
+    pub rows: usize,
+    pub cols: usize,
+    pub topology: Topology,
+    stones: [Bits; 4],
+    cells: Bits,
+    // Zobrist hash of the stones, kept up to date by every change to them
+    hash: u64,
 }
 
 impl Board {
-    // Initialize a new board of given size
+    // Initialize a new square board of given size
     // Size should default to 19x19 if not specified
     pub fn new(size:usize) -> Board {
+        Board::rectangular(size, size)
+    }
+
+    pub fn rectangular(rows: usize, cols: usize) -> Board {
+        assert!(rows <= MAX_SIZE && cols <= MAX_SIZE, "board size {}x{} exceeds maximum of {}", rows, cols, MAX_SIZE);
+        let len = rows * (cols + 1);
+        let mut cells = Bits::new(len);
+        for row in 0..rows {
+            for col in 0..cols {
+                cells.set(row * (cols + 1) + col);
+            }
+        }
         Board {
-            size,
-            grid: Array2::from_elem((size, size), Piece::Empty),
+            rows,
+            cols,
+            topology: Topology::Flat,
+            stones: std::array::from_fn(|_| Bits::new(len)),
+            cells,
+            hash: 0,
+        }
+    }
+
+    // A board whose opposite edges are joined, so it has no edges at all
+    pub fn toroidal(rows: usize, cols: usize) -> Board {
+        Board { topology: Topology::Torus, ..Board::rectangular(rows, cols) }
+    }
+
+    // An empty board with the same shape and topology
+    pub fn cleared(&self) -> Board {
+        Board { topology: self.topology, ..Board::rectangular(self.rows, self.cols) }
+    }
+
+    // Check whether (row, col) is on the board
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        row < self.rows && col < self.cols
+    }
+
+    // Distance between vertically adjacent cells, including the padding bit
+    fn stride(&self) -> usize {
+        self.cols + 1
+    }
+
+    fn index(&self, row: usize, col: usize) -> usize {
+        row * self.stride() + col
+    }
+
+    fn coords(&self, index: usize) -> (usize, usize) {
+        (index / self.stride(), index % self.stride())
+    }
+
+    fn occupied(&self) -> Bits {
+        self.stones[1..].iter().fold(self.stones[0].clone(), |acc, b| acc | b)
+    }
+
+    // Empty points of the board
+    fn free(&self) -> Bits {
+        !self.occupied() & &self.cells
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Piece {
+        let i = self.index(row, col);
+        PIECES.into_iter()
+            .find(|&piece| self.stones[slot(piece).unwrap()].get(i))
+            .unwrap_or(Piece::Empty)
+    }
+
+    pub fn is_empty(&self, row: usize, col: usize) -> bool {
+        let i = self.index(row, col);
+        !self.stones.iter().any(|bits| bits.get(i))
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, piece: Piece) {
+        let i = self.index(row, col);
+        let old = self.get(row, col);
+        if let Some(s) = slot(old) {
+            self.stones[s].clear(i);
+            self.hash ^= zobrist::stone(old, row, col);
+        }
+        if let Some(s) = slot(piece) {
+            self.stones[s].set(i);
+            self.hash ^= zobrist::stone(piece, row, col);
         }
     }
 
+    // Zobrist hash of the stones on the board
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
     pub fn get_moves(&self) -> Vec<(usize, usize)> {
-        let mut moves = Vec::new();
-        for row in 0..self.size {
-            for col in 0..self.size {
-                if self.grid[[row, col]] == Piece::Empty {
-                    moves.push((row, col));
+        self.free().ones().map(|i| self.coords(i)).collect()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.free().is_zero()
+    }
+
+    // Remove every run of stones of one capturable color sandwiched between (row, col)
+    // and another stone of `piece`, if the run's length is one of capture_sizes.
+    // Returns the removed stones and their colors.
+    pub fn capture(&mut self, row: usize, col: usize, piece: Piece, capture_sizes: RangeInclusive<usize>, capturable: impl Fn(Piece) -> bool) -> CapturedStones {
+        let Some(own) = slot(piece) else {
+            return Vec::new();
+        };
+        let mut captured = Vec::new();
+
+        for dir in [(0, 1), (0, -1), (1, 0), (-1, 0), (1, 1), (-1, -1), (1, -1), (-1, 1)] {
+            // Points 0..=max + 1 steps away, None past the edge of a flat board
+            let points: Vec<_> = (0..=*capture_sizes.end() as isize + 1)
+                .map(|k| self.offset(row, col, dir, k).map(|(r, c)| self.index(r, c)))
+                .collect();
+            let at = |k: usize| points[k];
+            for size in capture_sizes.clone() {
+                if !at(size + 1).is_some_and(|i| self.stones[own].get(i)) {
+                    continue;
+                }
+                let enemy = (0..self.stones.len())
+                    .filter(|&s| s != own && capturable(PIECES[s]))
+                    .find(|&s| (1..=size).all(|k| at(k).is_some_and(|i| self.stones[s].get(i))));
+                if let Some(enemy) = enemy {
+                    for i in (1..=size).filter_map(at) {
+                        let (r, c) = self.coords(i);
+                        self.stones[enemy].clear(i);
+                        self.hash ^= zobrist::stone(PIECES[enemy], r, c);
+                        captured.push(((r, c), PIECES[enemy]));
+                    }
+                    break;
                 }
             }
         }
-        moves
+        captured
+    }
+
+    // Remove every run through (row, col) of its own color whose length is one of
+    // capture_sizes and which is flanked on both ends by one capturable color, as
+    // when O fills the gap of X O _ X. Returns the flanking color of each removed
+    // run with its stones; the stone at (row, col) is only reported once.
+    pub fn poof(&mut self, row: usize, col: usize, capture_sizes: RangeInclusive<usize>, capturable: impl Fn(Piece) -> bool) -> Vec<(Piece, CapturedStones)> {
+        let piece = self.get(row, col);
+        let Some(own) = slot(piece) else {
+            return Vec::new();
+        };
+
+        // Find all runs before removing any, since they share the placed stone
+        let mut runs = Vec::new();
+        for dir in LINES {
+            let (back, forward) = self.run_extent(row, col, own, dir);
+            if !capture_sizes.contains(&(back + forward + 1)) {
+                continue;
+            }
+            let ends = [self.offset(row, col, dir, -(back as isize) - 1), self.offset(row, col, dir, forward as isize + 1)];
+            let flank = (0..self.stones.len())
+                .filter(|&s| s != own && capturable(PIECES[s]))
+                .find(|&s| ends.iter().all(|end| end.is_some_and(|(r, c)| self.stones[s].get(self.index(r, c)))));
+            if let Some(flank) = flank {
+                let run: Vec<_> = (-(back as isize)..=forward as isize)
+                    .filter_map(|k| self.offset(row, col, dir, k))
+                    .collect();
+                runs.push((PIECES[flank], run));
+            }
+        }
+
+        let mut poofed = Vec::new();
+        for (flank, run) in runs {
+            let mut removed = Vec::new();
+            for (r, c) in run {
+                let i = self.index(r, c);
+                if self.stones[own].get(i) {
+                    self.stones[own].clear(i);
+                    self.hash ^= zobrist::stone(piece, r, c);
+                    removed.push(((r, c), piece));
+                }
+            }
+            poofed.push((flank, removed));
+        }
+        poofed
+    }
+
+    // Point k steps from (row, col) along dir. On a flat board there is none past
+    // the edge, on a torus the board wraps around to the opposite edge.
+    pub fn offset(&self, row: usize, col: usize, dir: (isize, isize), k: isize) -> Option<(usize, usize)> {
+        let r = row as isize + k * dir.0;
+        let c = col as isize + k * dir.1;
+        match self.topology {
+            Topology::Flat => {
+                let (r, c) = (usize::try_from(r).ok()?, usize::try_from(c).ok()?);
+                self.contains(r, c).then_some((r, c))
+            }
+            Topology::Torus => Some((r.rem_euclid(self.rows as isize) as usize, c.rem_euclid(self.cols as isize) as usize)),
+        }
+    }
+
+    // Most points a line along dir can hold. On a torus a line comes back to
+    // where it started after that many steps.
+    fn period(&self, dir: (isize, isize)) -> usize {
+        match (self.topology, dir) {
+            (Topology::Flat, _) => self.rows.max(self.cols),
+            (Topology::Torus, (0, _)) => self.cols,
+            (Topology::Torus, (_, 0)) => self.rows,
+            (Topology::Torus, _) => self.rows / gcd(self.rows, self.cols) * self.cols,
+        }
+    }
+
+    // Stones of the given slot directly behind and ahead of (row, col) along dir.
+    // On a torus the run never counts a point twice.
+    fn run_extent(&self, row: usize, col: usize, s: usize, dir: (isize, isize)) -> (usize, usize) {
+        let stones = &self.stones[s];
+        let extent = |sign: isize, limit: usize| {
+            (1..=limit as isize)
+                .take_while(|&k| self.offset(row, col, dir, sign * k).is_some_and(|(r, c)| stones.get(self.index(r, c))))
+                .count()
+        };
+        let limit = self.period(dir) - 1;
+        let forward = extent(1, limit);
+        let back = extent(-1, limit - forward);
+        (back, forward)
+    }
+
+    // Find a run of at least num_in_a_row stones through (row, col) that share its
+    // color, returning every stone of the run from one end to the other
+    pub fn line_through(&self, row: usize, col: usize, num_in_a_row: usize) -> Option<Vec<(usize, usize)>> {
+        self.lines_through(row, col, num_in_a_row).next()
+    }
+
+    // Every run of at least num_in_a_row stones through (row, col) that share its
+    // color, at most one per direction, in the order of LINES
+    pub fn lines_through(&self, row: usize, col: usize, num_in_a_row: usize) -> impl Iterator<Item = Vec<(usize, usize)>> + '_ {
+        let s = slot(self.get(row, col));
+        s.into_iter().flat_map(move |s| {
+            LINES.into_iter().filter_map(move |dir| {
+                let (back, forward) = self.run_extent(row, col, s, dir);
+                (back + forward + 1 >= num_in_a_row).then(|| {
+                    (-(back as isize)..=forward as isize)
+                        .filter_map(|k| self.offset(row, col, dir, k))
+                        .collect()
+                })
+            })
+        })
+    }
+
+    // Check whether `piece` has num_in_a_row stones in a line anywhere on the board
+    pub fn has_line(&self, piece: Piece, num_in_a_row: usize) -> bool {
+        let Some(s) = slot(piece) else {
+            return false;
+        };
+        let stones = &self.stones[s];
+        if self.topology == Topology::Torus {
+            // Shifts don't wrap around, so walk the lines of every stone instead
+            return stones.ones().any(|i| {
+                let (row, col) = self.coords(i);
+                self.line_through(row, col, num_in_a_row).is_some()
+            });
+        }
+        let stride = self.stride();
+        [1, stride, stride + 1, stride - 1].into_iter().any(|step| {
+            let mut run = stones.clone();
+            for k in 1..num_in_a_row {
+                run &= &(stones >> (k * step));
+            }
+            !run.is_zero()
+        })
     }
 }
 
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
 impl fmt::Display for Board {
     fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
-        for row in 0..self.grid.shape()[0] {
-            for cell in 0..self.grid.shape()[1] {
-                write!(f, "{} ", cell)?;
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                write!(f, "{} ", self.get(row, col))?;
             }
             writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_moves() {
+        let mut board = Board::new(3);
+        board.set(1, 1, Piece::Black);
+        assert_eq!(board.get(1, 1), Piece::Black);
+        assert_eq!(board.get_moves().len(), 8);
+        assert!(!board.get_moves().contains(&(1, 1)));
+        board.set(1, 1, Piece::Empty);
+        assert!(board.is_empty(1, 1));
+        assert_eq!(board.get_moves().len(), 9);
+    }
+
+    #[test]
+    fn test_board_has_line() {
+        let mut board = Board::new(19);
+        for i in 0..5 {
+            board.set(14 + i, 18 - i, Piece::White);
+        }
+        assert!(board.has_line(Piece::White, 5));
+        assert!(!board.has_line(Piece::White, 6));
+        assert!(!board.has_line(Piece::Black, 5));
+
+        // A run split across the edge of the board must not count
+        let mut board = Board::new(19);
+        for col in 16..19 {
+            board.set(3, col, Piece::Black);
+        }
+        for col in 0..2 {
+            board.set(4, col, Piece::Black);
+        }
+        assert!(!board.has_line(Piece::Black, 5));
+    }
+
+    #[test]
+    fn test_board_line_through() {
+        let mut board = Board::new(MAX_SIZE);
+        for i in 0..5 {
+            board.set(2 + i, 6 - i, Piece::Black);
+        }
+        board.set(7, 1, Piece::White);
+        let expected: Vec<_> = (0..5).map(|i| (2 + i, 6 - i)).collect();
+        assert_eq!(board.line_through(4, 4, 5), Some(expected));
+        assert_eq!(board.line_through(4, 4, 6), None);
+        assert_eq!(board.line_through(7, 1, 5), None);
+        assert_eq!(board.line_through(0, 0, 5), None);
+    }
+
+    #[test]
+    fn test_board_capture_does_not_wrap() {
+        let mut board = Board::new(5);
+        board.set(0, 4, Piece::White);
+        board.set(1, 0, Piece::White);
+        board.set(1, 1, Piece::Black);
+        assert!(board.capture(0, 3, Piece::Black, 2..=2, |_| true).is_empty());
+        assert_eq!(board.get(0, 4), Piece::White);
+        assert_eq!(board.get(1, 0), Piece::White);
+    }
+
+    #[test]
+    fn test_board_poof() {
+        let mut board = Board::new(5);
+        board.set(2, 0, Piece::White);
+        board.set(2, 1, Piece::Black);
+        board.set(2, 3, Piece::White);
+        board.set(2, 2, Piece::Black);
+        let poofed = board.poof(2, 2, 2..=2, |piece| piece == Piece::White);
+        assert_eq!(poofed, vec![(Piece::White, vec![((2, 1), Piece::Black), ((2, 2), Piece::Black)])]);
+        assert!(board.is_empty(2, 1) && board.is_empty(2, 2));
+
+        // A triple is only poofed when triples can be captured
+        board.set(2, 1, Piece::Black);
+        board.set(2, 2, Piece::Black);
+        board.set(2, 3, Piece::Black);
+        board.set(2, 4, Piece::White);
+        assert!(board.poof(2, 2, 2..=2, |_| true).is_empty());
+        assert_eq!(board.poof(2, 2, 2..=3, |_| true).len(), 1);
+    }
+
+    #[test]
+    fn test_board_hash() {
+        let mut board = Board::new(9);
+        assert_eq!(board.hash(), 0);
+        board.set(4, 1, Piece::Black);
+        board.set(4, 2, Piece::White);
+        board.set(4, 3, Piece::White);
+        board.set(4, 4, Piece::Black);
+        assert_ne!(board.hash(), Board::new(9).hash());
+        board.capture(4, 4, Piece::Black, 2..=2, |_| true);
+
+        // The same stones placed in another order give the same hash
+        let mut expected = Board::new(9);
+        expected.set(4, 4, Piece::Black);
+        expected.set(4, 1, Piece::White);
+        expected.set(4, 1, Piece::Black);
+        assert_eq!(board.hash(), expected.hash());
+        expected.set(4, 1, Piece::Empty);
+        expected.set(4, 4, Piece::Empty);
+        assert_eq!(expected.hash(), 0);
+    }
+
+    #[test]
+    fn test_rectangular_and_large_boards() {
+        let mut board = Board::rectangular(3, 30);
+        assert_eq!(board.get_moves().len(), 90);
+        assert!(board.contains(2, 29) && !board.contains(3, 0) && !board.contains(0, 30));
+        for col in 25..30 {
+            board.set(2, col, Piece::Black);
+        }
+        assert!(board.has_line(Piece::Black, 5));
+        assert_eq!(board.line_through(2, 29, 5).map(|line| line.len()), Some(5));
+        // Only three rows, so no vertical line of five fits
+        for row in 0..3 {
+            board.set(row, 0, Piece::White);
+        }
+        assert!(!board.has_line(Piece::White, 4));
+        // A sandwich can't run off the bottom edge
+        board.set(1, 1, Piece::White);
+        board.set(2, 1, Piece::White);
+        assert!(board.capture(0, 1, Piece::Black, 2..=2, |_| true).is_empty());
+
+        let mut board = Board::new(40);
+        for k in 30..35 {
+            board.set(k, 69 - k, Piece::Red);
+        }
+        assert!(board.has_line(Piece::Red, 5));
+        assert!(board.line_through(34, 35, 5).is_some());
+        assert_eq!(board.get_moves().len(), 1600 - 5);
+
+        // Only boards bigger than 19x19 keep their bits on the heap
+        assert!(matches!(board.cells, Bits::Heap(_)));
+        assert!(matches!(Board::new(19).cells, Bits::Inline(_)));
+        assert!(matches!(Board::rectangular(3, 30).cells, Bits::Inline(_)));
+    }
+
+    #[test]
+    fn test_torus_wraps_lines_and_captures() {
+        let mut board = Board::toroidal(7, 9);
+        for col in [7, 8, 0, 1, 2] {
+            board.set(3, col, Piece::Black);
+        }
+        assert!(board.has_line(Piece::Black, 5));
+        assert_eq!(board.line_through(3, 0, 5), Some(vec![(3, 7), (3, 8), (3, 0), (3, 1), (3, 2)]));
+
+        // A diagonal crossing both edges
+        for k in 0..5 {
+            board.set((5 + k) % 7, (7 + k) % 9, Piece::Red);
+        }
+        assert_eq!(board.line_through(0, 0, 5).map(|line| line.len()), Some(5));
+
+        // A sandwich across the top edge
+        board.set(0, 5, Piece::White);
+        board.set(6, 5, Piece::White);
+        board.set(5, 5, Piece::Black);
+        assert_eq!(board.capture(1, 5, Piece::Black, 2..=2, |_| true), vec![((0, 5), Piece::White), ((6, 5), Piece::White)]);
+
+        // A row filled all the way around is one line of its length
+        let mut board = Board::toroidal(5, 5);
+        for col in 0..5 {
+            board.set(2, col, Piece::White);
+        }
+        assert_eq!(board.line_through(2, 2, 5).map(|line| line.len()), Some(5));
+        assert!(!board.has_line(Piece::White, 6));
+    }
+}
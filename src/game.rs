@@ -1,294 +1,1329 @@
-use std::io::{Read, Write};
+use std::fmt;
 use std::fs::File;
+use std::io::{Read, Write};
 use rand::Rng;
 
-use crate::board::Board;
-use crate::board::Piece;
-use crate::mcts_player::MCTSPlayer;
+use crate::action::Action;
+use crate::board::{Board, CapturedStones, Piece, MAX_SIZE};
+use crate::mcts_player::{MCTSPlayer, DEFAULT_EXPLORATION, DEFAULT_ITERATIONS};
+use crate::player::PlayerBehavior;
 use crate::random_player::get_piece_by_id;
-use crate::random_player::get_piece_id;
+use crate::rules::{MoveError, Position, Protocol, RuleSet};
+pub use crate::rules::{DrawReason, GameOutcome};
+use crate::symmetry::Symmetry;
+use crate::zobrist;
+
+// Pente uses one color per player, so at most four can take part
+pub const MAX_PLAYERS: usize = 4;
+
+// Stage of the game, which decides what kind of action is expected next
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Phase {
+    // Every player places stones of their own color
+    Play,
+    // The player to move places opening stones of alternating colors until
+    // `until` stones have been played
+    Placing { until: usize },
+    // The player to move answers Swap or Stay
+    SwapChoice,
+    // The player to move answers Swap, Stay or PlaceTwo
+    Swap2Choice,
+    // The player to move picks a side with ChooseColor
+    ColorChoice,
+}
+
+impl Phase {
+    pub fn start(protocol: Protocol) -> Phase {
+        match protocol {
+            Protocol::Standard => Phase::Play,
+            Protocol::Swap | Protocol::Swap2 => Phase::Placing { until: 3 },
+            Protocol::DPente => Phase::Placing { until: 4 },
+        }
+    }
+
+    // Choice the other player faces once the opening stones up to `until` are
+    // placed. After the first three stones of Swap2 they may also place two more.
+    fn after_placing(protocol: Protocol, until: usize) -> Phase {
+        match protocol {
+            Protocol::Swap2 if until == 3 => Phase::Swap2Choice,
+            Protocol::DPente => Phase::ColorChoice,
+            _ => Phase::SwapChoice,
+        }
+    }
+}
+
+// One ply of the game record, with everything needed to take the move back
+#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MoveRecord {
+    // Seat of the player who chose the action
+    pub player_idx: usize,
+    pub action: Action,
+    // Phase the action was played in
+    pub phase: Phase,
+    // Whether the action exchanged the colors of the two players
+    pub swapped: bool,
+    // Stones removed by the move
+    pub captured: CapturedStones,
+    // Stones of the mover removed by Poof-Pente suicide captures, with the seat
+    // credited with each run
+    pub poofed: Vec<(usize, CapturedStones)>,
+    // Captured stones of every player once the move was played
+    pub capture_totals: Vec<usize>,
+    // Game::quiet_moves before the move was played
+    pub quiet_moves: usize,
+}
+
+// Bump whenever the layout of SavedGame changes
+pub const SAVE_FORMAT_VERSION: u32 = 14;
+
+// Everything written to a game file after the version number
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedGame {
+    // Color of each seat at the start of the game, before any swap
+    pieces: Vec<Piece>,
+    rules: RuleSet,
+    moves: Vec<MoveRecord>,
+    // Final position, checked against the replayed moves on load
+    board: Board,
+    player_idx: usize,
+    turn: usize,
+    phase: Phase,
+}
 
-// Define struct for game outcomes
-pub struct GameOutcome {
-    pub is_game_over: bool,
-    pub is_draw: bool,
-    pub winner: usize,
+#[derive(Debug)]
+pub enum GameFileError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    VersionMismatch { found: u32, expected: u32 },
+    // The file decoded but its moves don't lead to the stored final state
+    Corrupt(String),
 }
 
+impl fmt::Display for GameFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameFileError::Io(e) => write!(f, "I/O error: {}", e),
+            GameFileError::Encoding(e) => write!(f, "encoding error: {}", e),
+            GameFileError::VersionMismatch { found, expected } => {
+                write!(f, "game file has format version {}, expected {}", found, expected)
+            }
+            GameFileError::Corrupt(e) => write!(f, "corrupt game file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GameFileError {}
+
+impl From<std::io::Error> for GameFileError {
+    fn from(e: std::io::Error) -> GameFileError {
+        GameFileError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for GameFileError {
+    fn from(e: bincode::Error) -> GameFileError {
+        GameFileError::Encoding(e)
+    }
+}
 
 // Implement a game struct that has a board and players
 #[derive(Clone)]
 pub struct Game {
     // pub boards: Vec<Board>,
     pub board: Board,
-    pub players: Vec<MCTSPlayer>,
+    pub rules: RuleSet,
+    pub players: Vec<Box<dyn PlayerBehavior>>,
+    // Captured stones of each player, indexed like players
+    pub captures: Vec<usize>,
     pub player_idx: usize,
+    // Number of stones played so far
     pub turn: usize,
+    // Stones played in a row without a threat, for RuleSet::quiet_moves_to_draw
+    pub quiet_moves: usize,
+    pub phase: Phase,
+    // Set once step ends the game, after which no action is accepted
+    pub is_over: bool,
+    // Every move played so far, oldest first
+    pub history: Vec<MoveRecord>,
 }
 
 impl Game {
     // Initialize a new game with a board and num_players players
     pub fn new(size: usize, num_players: usize) -> Game {
+        Game::with_rules(size, num_players, RuleSet::default())
+    }
+
+    // Initialize a new game played under the given rule variant
+    pub fn with_rules(size: usize, num_players: usize, rules: RuleSet) -> Game {
+        Game::on_board(Board::new(size), rules, Game::default_players(num_players))
+    }
+
+    // Initialize a new game between any mix of players, who move in the given order
+    pub fn with_players(size: usize, rules: RuleSet, players: Vec<Box<dyn PlayerBehavior>>) -> Game {
+        Game::on_board(Board::new(size), rules, players)
+    }
+
+    // Initialize a new game on an empty board of any shape, such as Board::rectangular
+    pub fn on_board(board: Board, rules: RuleSet, players: Vec<Box<dyn PlayerBehavior>>) -> Game {
+        assert!((2..=MAX_PLAYERS).contains(&players.len()), "a game needs between 2 and {} players", MAX_PLAYERS);
+        assert!(rules.protocol == Protocol::Standard || players.len() == 2, "swap openings need exactly 2 players");
+        let pieces: Vec<Piece> = players.iter().map(|p| p.get_piece()).collect();
+        if let Err(e) = Game::check_colors(&rules, &pieces) {
+            panic!("{}", e);
+        }
+        let phase = Phase::start(rules.protocol);
         Game {
             // boards: Vec::new(),
-            board: Board::new(size),
-            // Give every player a different Piece type
-            players: vec![MCTSPlayer::new(0, get_piece_by_id(0), 1, 0), MCTSPlayer::new(1, get_piece_by_id(1), 1, 0)],
+            board,
+            rules,
+            captures: vec![0; players.len()],
+            players,
             player_idx: 0,
             turn: 0,
+            quiet_moves: 0,
+            phase,
+            is_over: false,
+            history: Vec::new(),
         }
     }
 
+    // Stones and captures are credited to a seat through its color, so every
+    // seat needs its own, and swap openings hand over Black and White
+    fn check_colors(rules: &RuleSet, pieces: &[Piece]) -> Result<(), &'static str> {
+        let distinct = pieces.iter().enumerate().all(|(i, piece)| *piece != Piece::Empty && !pieces[..i].contains(piece));
+        if !distinct {
+            return Err("every player needs a color of their own");
+        }
+        let black_and_white = pieces.contains(&Piece::Black) && pieces.contains(&Piece::White);
+        if rules.protocol != Protocol::Standard && !black_and_white {
+            return Err("swap openings are played with Black and White");
+        }
+        Ok(())
+    }
+
+    // MCTS players with default settings, each with a different Piece type
+    fn default_players(num_players: usize) -> Vec<Box<dyn PlayerBehavior>> {
+        let pieces: Vec<Piece> = (0..num_players).map(get_piece_by_id).collect();
+        Game::players_for(&pieces)
+    }
+
+    // MCTS players with default settings playing the given colors in seat order
+    fn players_for(pieces: &[Piece]) -> Vec<Box<dyn PlayerBehavior>> {
+        pieces.iter()
+            .enumerate()
+            .map(|(id, &piece)| {
+                let player = MCTSPlayer::new(id, piece, DEFAULT_ITERATIONS, DEFAULT_EXPLORATION);
+                Box::new(player) as Box<dyn PlayerBehavior>
+            })
+            .collect()
+    }
+
+    // Color of each seat at the start of the game, undoing the swaps of the opening
+    pub fn starting_pieces(&self) -> Vec<Piece> {
+        let mut pieces = self.pieces();
+        for _ in self.history.iter().filter(|record| record.swapped) {
+            pieces.swap(0, 1);
+        }
+        pieces
+    }
+
     pub fn reset(&mut self, size: usize, num_players: usize) -> Game {
-        Game {
-            // boards: Vec::new(),
-            board: Board::new(size),
-            // Give every player a different Piece type
-            players: vec![MCTSPlayer::new(0, get_piece_by_id(0), 1, 0), MCTSPlayer::new(1, get_piece_by_id(1), 1, 0)],
-            player_idx: 0,
-            turn: 0,
+        Game::with_rules(size, num_players, self.rules.clone())
+    }
+
+    // Empty intersections the player to move is allowed to play under the current
+    // rules. There are none while a choice of colors is pending or once the game is over.
+    pub fn legal_moves(&self) -> Vec<(usize, usize)> {
+        if self.is_over || !matches!(self.phase, Phase::Play | Phase::Placing { .. }) {
+            return Vec::new();
+        }
+        let mut moves = self.board.get_moves();
+        moves.retain(|&(x, y)| self.allows(x, y));
+        moves
+    }
+
+    // Every action the player to move may take
+    pub fn legal_actions(&self) -> Vec<Action> {
+        if self.is_over {
+            return Vec::new();
+        }
+        match self.phase {
+            Phase::Play | Phase::Placing { .. } => self.legal_moves().into_iter().map(Action::from).collect(),
+            Phase::SwapChoice => vec![Action::Swap, Action::Stay],
+            Phase::Swap2Choice => vec![Action::Swap, Action::Stay, Action::PlaceTwo],
+            Phase::ColorChoice => vec![Action::ChooseColor(Piece::Black), Action::ChooseColor(Piece::White)],
+        }
+    }
+
+    // Legality of every action, indexed by Action::index, for masking a policy
+    pub fn action_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; Action::space_size(&self.board)];
+        for action in self.legal_actions() {
+            mask[action.index(&self.board)] = true;
+        }
+        mask
+    }
+
+    // Zobrist hash of the position: the stones of each color, the color to move,
+    // the stones captured by each color and any opening phase under way. Seats
+    // don't count, so it doesn't matter who ended up with which color.
+    pub fn hash(&self) -> u64 {
+        let captures = self.players.iter()
+            .zip(&self.captures)
+            .fold(0, |hash, (player, &count)| hash ^ zobrist::captures(player.get_piece(), count));
+        let side_to_move = zobrist::side_to_move(self.players[self.player_idx].get_piece());
+        self.board.hash() ^ side_to_move ^ captures ^ zobrist::phase(self.phase)
+    }
+
+    // Hash shared by every rotation and reflection of the position, for looking
+    // up positions regardless of orientation
+    pub fn canonical_hash(&self) -> u64 {
+        let stones = self.board.hash();
+        Symmetry::of(&self.board).into_iter()
+            .map(|s| s.board(&self.board).hash())
+            .min()
+            .unwrap()
+            ^ stones ^ self.hash()
+    }
+
+    // Stones the player to move still places before the turn passes
+    pub fn stones_left_in_turn(&self) -> usize {
+        self.rules.stones_left_in_turn(self.turn)
+    }
+
+    // Seat of the player who plays the given color
+    fn seat_of(&self, piece: Piece) -> usize {
+        self.players.iter().position(|p| p.get_piece() == piece).unwrap()
+    }
+
+    // Seat whose color the next stone has. While opening stones are being
+    // placed this isn't the player to move.
+    fn seat_to_place(&self) -> usize {
+        match self.phase {
+            Phase::Placing { .. } => self.seat_of(get_piece_by_id(self.turn % self.players.len())),
+            _ => self.player_idx,
         }
     }
 
-    // Define a MCTS rollout function that plays n games from the current game state, and returns each outcome.
-    pub fn rollout(&mut self, n: usize) -> (usize, usize, usize){
-        let mut winner_0_count = 0;
-        let mut winner_1_count = 0;
+    // Give each of the two players the other's color, along with the stones
+    // captured by that color so far
+    fn swap_colors(&mut self) {
+        let (first, second) = (self.players[0].get_piece(), self.players[1].get_piece());
+        self.players[0].set_piece(second);
+        self.players[1].set_piece(first);
+        self.captures.swap(0, 1);
+    }
+
+    // Check whether the rules let the next stone go on the empty point (x, y)
+    fn allows(&self, x: usize, y: usize) -> bool {
+        let piece = self.players[self.seat_to_place()].get_piece();
+        self.rules.allows(&self.board, self.turn, self.players.len(), piece, x, y)
+    }
+
+    // Define a MCTS rollout function that plays n random games from the current game state,
+    // and returns the number of wins of each player and the number of draws
+    pub fn rollout(&mut self, n: usize) -> (Vec<usize>, usize) {
+        let mut win_counts = vec![0; self.players.len()];
         let mut is_draw_count = 0;
 
         for _ in 0..n {
             let mut game = self.clone();
-            let (board, reward, done, outcome) = game.run(true);
-            if outcome.is_draw {
-                is_draw_count += 1;
-            } else if outcome.winner == 0 {
-                winner_0_count += 1;
-            } else if outcome.winner == 1 {
-                winner_1_count += 1;
+            let Ok((_, _, _, outcome)) = game.run(true) else {
+                break;
+            };
+            match outcome.winner {
+                Some(winner) => win_counts[winner] += 1,
+                None => is_draw_count += 1,
             }
         }
-        return (winner_0_count, winner_1_count, is_draw_count)
+        (win_counts, is_draw_count)
     }
 
-    // Make an explore function that runs a rollout for each valid action
-    pub fn explore(&mut self, rollouts_per_move: usize) -> Vec<(usize, usize)> {
-        let mut valid_actions = self.board.get_moves();
-        let mut action_scores = vec![0; valid_actions.len()];
-        for (i, action) in valid_actions.iter().enumerate() {
-            let mut total_score = 0;
-            let (winner_0_count, winner_1_count, is_draw_count) = self.rollout(rollouts_per_move);
-            if self.player_idx == 0 {
-                total_score = winner_0_count / 100;
-            } else if self.player_idx == 1 {
-                total_score = winner_1_count / 100;
-            }
-            action_scores[i] = total_score;
-        }
-        // Assuming action_scores is a Vec<usize> and valid_actions is a Vec of the same length
-        let mut best_score = 0;
-        let mut best_action = valid_actions[0];
-        for (i, score) in action_scores.iter().enumerate() {
-            if *score > best_score {
-                best_score = *score;
-                best_action = valid_actions[i];
-            }
+    // Check whether the players at two seats play on the same side
+    pub fn are_partners(&self, a: usize, b: usize) -> bool {
+        self.rules.are_partners(self.players[a].get_piece(), self.players[b].get_piece())
+    }
+
+    // Captured stones of a player, plus those of its partner in team games
+    pub fn side_captures(&self, seat: usize) -> usize {
+        self.rules.side_captures(&self.pieces(), &self.captures, seat)
+    }
+
+    // Color of the player at each seat
+    fn pieces(&self) -> Vec<Piece> {
+        self.players.iter().map(|p| p.get_piece()).collect()
+    }
+
+    // Color of the next stone, unless a choice of colors is pending
+    fn next_stone(&self) -> Option<Piece> {
+        match self.phase {
+            Phase::Play | Phase::Placing { .. } => Some(self.players[self.seat_to_place()].get_piece()),
+            _ => None,
         }
-        return vec![best_action];
     }
 
-    // Define a function that checks if the game is over
-    pub fn is_game_over(&self, board: &Board, num_in_a_row: usize, num_captured_pairs: usize) -> GameOutcome {
-        // Return GameOutcome
-        // 1. Check if there are num_in_a_row pieces (of the same color) in a row on diagonals, horizontal, or vertical
-        let player = &self.players[self.player_idx];
+    // Define a function that checks if the game is over after the player at seat
+    // `mover` placed a stone at last_move, the last move of history
+    pub fn is_game_over(&self, board: &Board, last_move: (usize, usize), mover: usize) -> GameOutcome {
+        let pieces = self.pieces();
+        let previous_move = match self.history.iter().rev().nth(1).map(|record| record.action) {
+            Some(Action::Place(x, y)) => Some((x, y)),
+            _ => None,
+        };
+        let position = Position {
+            board,
+            pieces: &pieces,
+            captures: &self.captures,
+            turn: self.turn,
+            quiet_moves: self.quiet_moves,
+            next_stone: self.next_stone(),
+            previous_move,
+        };
+        self.rules.outcome(&position, last_move, mover)
+    }
 
-        for row in 0..board.size {
-            for col in 0..board.size {
-                let piece = &board.grid[[row, col]];
-                if *piece == Piece::Empty {
-                    continue;
-                }
-                // Check horizontal
-                if col + num_in_a_row <= board.size {
-                    let mut is_win = true;
-                    for i in 1..num_in_a_row {
-                        if board.grid[[row, col + i]] != *piece {
-                            is_win = false;
-                            break;
-                        }
-                    }
-                    if is_win {
-                        return GameOutcome {
-                            is_game_over: true,
-                            winner: player.id,
-                            is_draw: false,
-                        };
-                    }
-                }
-                // Check vertical
-                if row + num_in_a_row <= board.size {
-                    let mut is_win = true;
-                    for i in 1..num_in_a_row {
-                        if board.grid[[row + i, col]] != *piece {
-                            is_win = false;
-                            break;
-                        }
-                    }
-                    if is_win {
-                        return GameOutcome {
-                            is_game_over: true,
-                            winner: player.id,
-                            is_draw: false,
-                        };
-                    }
-                }
-                // Check diagonal
-                
-                if col + num_in_a_row <= board.size && row + num_in_a_row <= board.size {
-                    let mut is_win = true;
-                    for i in 1..num_in_a_row {
-                        if board.grid[[row + i, col + i]] != *piece {
-                            is_win = false;
-                            break;
-                        }
-                    }
-                    if is_win {
-                        return GameOutcome {
-                            is_game_over: true,
-                            winner: player.id, // Ensure get_piece_id is defined
-                            is_draw: false,
-                        };
-                    }
+    // Play an action for the player to move and pass the turn, returning what is
+    // needed to take the action back with unmake_move
+    pub fn make_move(&mut self, action: impl Into<Action>) -> Result<MoveRecord, MoveError> {
+        if self.is_over {
+            return Err(MoveError::GameOver);
+        }
+        let action = action.into();
+        let phase = self.phase;
+        let player_idx = self.player_idx;
+        let quiet_moves = self.quiet_moves;
+        let mut captured = Vec::new();
+        let mut poofed = Vec::new();
+        let swapped = match (action, phase) {
+            (Action::Place(x, y), Phase::Play | Phase::Placing { .. }) => {
+                // Bounds, occupancy and forbidden points are checked by apply_move
+                let on_empty_point = self.board.contains(x, y) && self.board.is_empty(x, y);
+                if on_empty_point && !self.rules.opening_allows(&self.board, self.turn, self.players.len(), x, y) {
+                    return Err(MoveError::Forbidden);
                 }
-                // Check anti-diagonal
-                if col + 1 >= num_in_a_row && row + num_in_a_row <= board.size {
-                    let mut is_win = true;
-                    for i in 1..num_in_a_row {
-                        if board.grid[[row + i, col - i]] != *piece {
-                            is_win = false;
-                            break;
-                        }
-                    }
-                    if is_win {
-                        return GameOutcome {
-                            is_game_over: true,
-                            winner: player.id, // Ensure get_piece_id is defined
-                            is_draw: false,
-                        };
-                    }
+                let seat = self.seat_to_place();
+                let result = self.rules.apply_move(&mut self.board, x, y, self.players[seat].get_piece())?;
+                captured = result.captured;
+                self.captures[seat] += captured.len();
+                for (flank, stones) in result.poofed {
+                    let credited = self.seat_of(flank);
+                    self.captures[credited] += stones.len();
+                    poofed.push((credited, stones));
                 }
+                let threat = !captured.is_empty()
+                    || !poofed.is_empty()
+                    || self.board.line_through(x, y, self.rules.threat_length()).is_some();
+                self.quiet_moves = if threat { 0 } else { quiet_moves + 1 };
+                self.turn += 1;
+                self.advance_after_stone();
+                false
             }
-        }
+            (Action::Swap | Action::Stay, Phase::SwapChoice | Phase::Swap2Choice) => {
+                self.finish_opening(action == Action::Swap)
+            }
+            (Action::ChooseColor(piece), Phase::ColorChoice) if self.players.iter().any(|p| p.get_piece() == piece) => {
+                self.finish_opening(self.players[player_idx].get_piece() != piece)
+            }
+            (Action::PlaceTwo, Phase::Swap2Choice) => {
+                self.phase = Phase::Placing { until: self.turn + 2 };
+                false
+            }
+            _ => return Err(MoveError::WrongPhase),
+        };
+        let capture_totals = self.captures.clone();
+        Ok(MoveRecord { player_idx, action, phase, swapped, captured, poofed, capture_totals, quiet_moves })
+    }
 
-        // 2. Check if current player has captured num_captured_pairs of other players pieces
-        if player.captured_pairs >= num_captured_pairs {
-            // println!("Player {} wins by capturing {} pairs!", player.id, num_captured_pairs);
-            return GameOutcome {
-                is_game_over: true,
-                winner: player.id,
-                is_draw: false,
-            };
+    // Settle the colors at the end of a swap opening and start normal play,
+    // returning whether the players exchanged colors
+    fn finish_opening(&mut self, swap: bool) -> bool {
+        if swap {
+            self.swap_colors();
         }
+        // Play continues with whoever now has the color of the next stone
+        self.phase = Phase::Play;
+        self.player_idx = self.seat_of(get_piece_by_id(self.turn % self.players.len()));
+        swap
+    }
 
+    // Pass the turn once a stone has been placed, unless the turn has more stones
+    fn advance_after_stone(&mut self) {
+        match self.phase {
+            Phase::Play if self.rules.stones_left_in_turn(self.turn - 1) == 1 => {
+                self.player_idx = (self.player_idx + 1) % self.players.len();
+            }
+            Phase::Placing { until } if self.turn == until => {
+                self.phase = Phase::after_placing(self.rules.protocol, until);
+                self.player_idx = (self.player_idx + 1) % self.players.len();
+            }
+            _ => {}
+        }
+    }
 
-        // 3. Check if the board is full
-        let mut is_full = true;
-        for row in 0..board.size {
-            for col in 0..board.size {
-                if board.grid[[row, col]] == Piece::Empty {
-                    is_full = false;
-                    break;
+    // Exactly reverse a move returned by make_move. Moves must be unmade in the
+    // reverse order they were made.
+    pub fn unmake_move(&mut self, record: &MoveRecord) {
+        match record.action {
+            Action::Place(x, y) => {
+                for (credited, stones) in &record.poofed {
+                    for &((px, py), piece) in stones {
+                        self.board.set(px, py, piece);
+                    }
+                    self.captures[*credited] -= stones.len();
+                }
+                let seat = self.seat_of(self.board.get(x, y));
+                self.board.set(x, y, Piece::Empty);
+                for &((cx, cy), piece) in &record.captured {
+                    self.board.set(cx, cy, piece);
                 }
+                self.captures[seat] -= record.captured.len();
+                self.turn -= 1;
             }
+            _ if record.swapped => self.swap_colors(),
+            _ => {}
         }
+        self.phase = record.phase;
+        self.player_idx = record.player_idx;
+        self.quiet_moves = record.quiet_moves;
+        self.is_over = false;
+    }
 
-        if is_full {
-            return GameOutcome {
-                is_game_over: true,
-                winner: 100,
-                is_draw: true,
-            };
-        } else {
-            return GameOutcome {
-                is_game_over: false,
-                winner: 100,
-                is_draw: false,
-            };
+    // Take back the last move played with step, if any
+    pub fn undo(&mut self) -> Option<MoveRecord> {
+        let record = self.history.pop()?;
+        self.unmake_move(&record);
+        Some(record)
+    }
+
+    // Iterate over the moves played so far, oldest first
+    pub fn moves(&self) -> impl Iterator<Item = &MoveRecord> {
+        self.history.iter()
+    }
+
+    // Rebuild a game by playing recorded moves on an empty board shaped like
+    // `board`, with seats starting with the given colors. Fails if any move can't
+    // be played or doesn't reproduce its record.
+    pub fn replay<'a>(board: &Board, pieces: &[Piece], rules: RuleSet, moves: impl IntoIterator<Item = &'a MoveRecord>) -> Result<Game, String> {
+        if !(2..=MAX_PLAYERS).contains(&pieces.len()) {
+            return Err(format!("A game can't have {} players", pieces.len()));
         }
+        Game::check_colors(&rules, pieces).map_err(|e| e.to_string())?;
+        let mut game = Game::on_board(board.cleared(), rules, Game::players_for(pieces));
+        for (ply, record) in moves.into_iter().enumerate() {
+            if record.player_idx != game.player_idx {
+                return Err(format!("Move {} was recorded for player {} but player {} is to move", ply, record.player_idx, game.player_idx));
+            }
+            game.step(record.action).map_err(|e| format!("Move {} can't be played: {}", ply, e))?;
+            if game.history.last() != Some(record) {
+                return Err(format!("Move {} does not reproduce the recorded captures", ply));
+            }
+        }
+        Ok(game)
     }
 
     // Implement a step function that conforms to the GYM reinforcement learning API standard
-    pub fn step(&mut self, action: (usize, usize)) -> (Board, f32, bool, GameOutcome) {
-        // 1. Check if the action is valid
+    pub fn step(&mut self, action: impl Into<Action>) -> Result<(Board, f32, bool, GameOutcome), MoveError> {
+        // 1. Check if the action is valid, returning the error and leaving the game unchanged if not
         // 2. If the action is valid, apply it to the board
         // 3. Check if the game is over
         // 4. If the game is over, return the board, reward, and done
         // 5. If the game is not over, return the board, 0 reward, and not done
-        let player = &mut self.players[self.player_idx];
-        let (x, y) = action;
+        // Seat whose color the stone has, if the action places one
+        let placer = self.seat_to_place();
+        let record = self.make_move(action)?;
+        // The reward returned is the one of the player who just moved
+        let mover = record.player_idx;
+        let action = record.action;
+        self.history.push(record);
+        let outcome = match action {
+            Action::Place(x, y) => self.is_game_over(&self.board, (x, y), placer),
+            _ => GameOutcome::ongoing(self.players.len()),
+        };
+        self.is_over = outcome.is_game_over;
 
-        // self.boards.push(self.board.clone());
-        if let Err(e) = player.act(&mut self.board, x, y) {
-            println!("MCTSPlayer {} failed to act: {}", 0, e);
-        }
-        let outcome = self.is_game_over(&self.board, 5, 5);
+        Ok((self.board.clone(), outcome.rewards[mover], outcome.is_game_over, outcome))
+    }
 
-        if outcome.is_game_over && !outcome.is_draw {
-            return (self.board.clone(), outcome.winner as f32, true, outcome);
-        }
-        self.turn += 1;
-        (self.board.clone(), 0.0, false, outcome)
+    // Write the rules, move record and final state to a binary file using bincode
+    pub fn save(&self, file_path: &str) -> Result<(), GameFileError> {
+        let saved = SavedGame {
+            pieces: self.starting_pieces(),
+            rules: self.rules.clone(),
+            moves: self.history.clone(),
+            board: self.board.clone(),
+            player_idx: self.player_idx,
+            turn: self.turn,
+            phase: self.phase,
+        };
+        let serialized = bincode::serialize(&(SAVE_FORMAT_VERSION, saved))?;
+        let mut file = File::create(file_path)?;
+        file.write_all(&serialized)?;
+        Ok(())
     }
 
-    // // Write Game to binary file using bincode
-    // pub fn save(&mut self, file_path: &str) {
-    //     let target: Option<Game> = Some(self.clone());
-    //     let serialized = bincode::serialize(&target).unwrap();
-    //     let mut file = std::fs::File::create(file_path).unwrap();
-    //     file.write_all(&serialized).unwrap();
-    // }
+    // Load a game written by save() by replaying its moves. Players are not
+    // stored, so the loaded game gets the default players.
+    pub fn load(file_path: &str) -> Result<Game, GameFileError> {
+        let mut file = File::open(file_path)?;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)?;
 
-    // // Load Game from binary file using bincode
-    // pub fn load(file_path: &str) -> Result<Game, Box<dyn std::error::Error>> {
-    //     let mut file = File::open(file_path)?;
-    //     let mut buffer = Vec::new();
-    //     file.read_to_end(&mut buffer)?;
+        let found: u32 = bincode::deserialize(&buffer)?;
+        if found != SAVE_FORMAT_VERSION {
+            return Err(GameFileError::VersionMismatch { found, expected: SAVE_FORMAT_VERSION });
+        }
+        let (_, saved): (u32, SavedGame) = bincode::deserialize(&buffer)?;
+        let (rows, cols) = (saved.board.rows, saved.board.cols);
+        if rows > MAX_SIZE || cols > MAX_SIZE {
+            return Err(GameFileError::Corrupt(format!("unsupported {}x{} board", rows, cols)));
+        }
 
-    //     let game: Game = bincode::deserialize(&buffer)?;
-    //     Ok(game)
-    // }
+        let game = Game::replay(&saved.board, &saved.pieces, saved.rules, &saved.moves)
+            .map_err(GameFileError::Corrupt)?;
+        if game.board != saved.board || game.player_idx != saved.player_idx || game.turn != saved.turn || game.phase != saved.phase {
+            return Err(GameFileError::Corrupt("moves do not lead to the saved position".to_string()));
+        }
+        Ok(game)
+    }
 
-    // Use step() in a loop to run a game
-    pub fn run(mut self, random: bool) -> (Board, f32, bool, GameOutcome) {
+    // Use step() in a loop to run a game, stopping at the first action that can't be played
+    pub fn run(&mut self, random: bool) -> Result<(Board, f32, bool, GameOutcome), MoveError> {
         let mut done = false;
         let mut reward = 0.0;
         let mut board = self.board.clone();
-        let mut outcome = GameOutcome {
-            is_game_over: false,
-            winner: 100,
-            is_draw: false,
-        };
-    
+        let mut outcome = GameOutcome::ongoing(self.players.len());
+        let mut rng = rand::thread_rng();
+
         while !done {
-            let mut action = (0, 0);
-            if !random {
+            let action = if !random {
                 let player = &self.players[self.player_idx];
-                action = player.think(self.clone());
+                player.think(self)
             } else {
-                let mut rng = rand::thread_rng();
-                let mut valid_actions = self.board.get_moves();
-                action = valid_actions[rng.gen_range(0..valid_actions.len())];
-            }
-        
-            let (new_board, new_reward, new_done, new_outcome) = self.step(action);
+                let valid_actions = self.legal_actions();
+                valid_actions[rng.gen_range(0..valid_actions.len())]
+            };
+
+            let (new_board, new_reward, new_done, new_outcome) = self.step(action)?;
             board = new_board;
             reward = new_reward;
             done = new_done;
             outcome = new_outcome;
-            self.player_idx = (self.player_idx + 1) % self.players.len();
         }
         // println!("Player {} wins!", self.player_idx);
-        (board, reward, done, outcome)
+        Ok((board, reward, done, outcome))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random_player::RandomPlayer;
+    use crate::rules::{Opening, Protocol};
+
+    #[test]
+    fn test_win_credited_to_line_owner() {
+        let mut game = Game::new(19, 2);
+        // White builds a line while Black plays elsewhere; the last stone is White's
+        for i in 0..5 {
+            game.player_idx = 0;
+            game.step((10, 2 * i)).unwrap();
+            game.player_idx = 1;
+            let (_, _, done, outcome) = game.step((5, 3 + i)).unwrap();
+            assert_eq!(done, i == 4);
+            if done {
+                assert_eq!(outcome.winner, Some(1));
+                assert_eq!(outcome.rewards, vec![-1.0, 1.0]);
+                let line: Vec<_> = (3..8).map(|col| (5, col)).collect();
+                assert_eq!(outcome.winning_line, Some(line));
+            }
+        }
+    }
+
+    #[test]
+    fn test_overline_only_wins_when_allowed() {
+        let rules = RuleSet { overline_wins: false, ..RuleSet::pente() };
+        let mut game = Game::with_rules(19, 2, rules);
+        for (i, col) in [0, 1, 2, 4, 5].into_iter().enumerate() {
+            game.step((3, col)).unwrap();
+            game.step((10, 2 * i)).unwrap();
+        }
+        let (_, _, done, _) = game.step((3, 3)).unwrap();
+        assert!(!done);
+
+        game.undo();
+        game.rules.overline_wins = true;
+        let (_, _, done, outcome) = game.step((3, 3)).unwrap();
+        assert!(done);
+        assert_eq!(outcome.winning_line.map(|line| line.len()), Some(6));
+    }
+
+    #[test]
+    fn test_move_limit_and_opening() {
+        let rules = RuleSet { max_moves: Some(3), opening: Opening::Center, ..RuleSet::pente() };
+        let mut game = Game::with_rules(9, 2, rules);
+        assert_eq!(game.legal_moves(), vec![(4, 4)]);
+        game.step((4, 4)).unwrap();
+        assert_eq!(game.legal_moves().len(), 80);
+        game.step((0, 0)).unwrap();
+        let (_, _, done, outcome) = game.step((8, 8)).unwrap();
+        assert!(done);
+        assert!(outcome.is_draw);
+        assert_eq!(outcome.draw_reason, Some(DrawReason::MoveLimit));
+    }
+
+    #[test]
+    fn test_no_legal_moves_draw() {
+        // No point of a 5x5 board is three intersections from the center, so
+        // Black has nowhere to put the second stone
+        let mut game = Game::with_rules(5, 2, RuleSet::tournament_pente());
+        let (_, _, done, outcome) = game.run(true).unwrap();
+        assert!(done && outcome.is_draw);
+        assert_eq!(outcome.draw_reason, Some(DrawReason::NoLegalMoves));
+        assert_eq!(game.turn, 2);
+
+        let mut game = Game::with_rules(7, 2, RuleSet::tournament_pente());
+        game.step((3, 3)).unwrap();
+        let (_, _, done, _) = game.step((3, 4)).unwrap();
+        assert!(!done);
+        assert!(game.legal_moves().iter().all(|&(x, y)| x.abs_diff(3).max(y.abs_diff(3)) == 3));
+    }
+
+    #[test]
+    fn test_undo_restores_captures() {
+        let mut game = Game::new(9, 2);
+        let start = game.board.clone();
+        // Black at (4, 1) and (4, 4) sandwiches the white pair at (4, 2) and (4, 3)
+        for action in [(4, 1), (4, 2), (0, 0), (4, 3), (4, 4)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.captures[0], 2);
+        assert!(game.board.is_empty(4, 2) && game.board.is_empty(4, 3));
+        let before = game.board.clone();
+
+        let record = game.undo().unwrap();
+        assert_eq!(record.captured, vec![((4, 3), Piece::White), ((4, 2), Piece::White)]);
+        assert_eq!(game.board.get(4, 2), Piece::White);
+        assert_eq!(game.captures[0], 0);
+        assert_eq!((game.turn, game.player_idx), (4, 0));
+
+        game.step((4, 4)).unwrap();
+        assert_eq!(game.board, before);
+        while game.undo().is_some() {}
+        assert_eq!(game.board, start);
+        assert_eq!((game.turn, game.player_idx), (0, 0));
+    }
+
+    #[test]
+    fn test_replay_reproduces_game() {
+        let mut game = Game::new(9, 2);
+        game.run(true).unwrap();
+        assert_eq!(game.moves().count(), game.turn);
+        assert_eq!(game.moves().last().unwrap().capture_totals, game.captures);
+
+        let replayed = Game::replay(&game.board, &game.starting_pieces(), game.rules.clone(), game.moves()).unwrap();
+        assert_eq!(replayed.board, game.board);
+        assert_eq!(replayed.history, game.history);
+        assert_eq!((replayed.turn, replayed.player_idx), (game.turn, game.player_idx));
+
+        let mut moves = game.history.clone();
+        moves.swap(0, 1);
+        assert!(Game::replay(&game.board, &game.starting_pieces(), game.rules.clone(), &moves).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("fast_pente_test_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut game = Game::new(9, 2);
+        game.run(true).unwrap();
+        game.save(path).unwrap();
+        let loaded = Game::load(path).unwrap();
+        assert_eq!(loaded.board, game.board);
+        assert_eq!(loaded.history, game.history);
+        assert_eq!(loaded.rules, game.rules);
+
+        // Seats keep their colors, whatever they are and however the opening went
+        for (rules, pieces) in [
+            (RuleSet::default(), [Piece::White, Piece::Black]),
+            (RuleSet::default(), [Piece::Red, Piece::Green]),
+            (RuleSet { protocol: Protocol::Swap, ..RuleSet::default() }, [Piece::White, Piece::Black]),
+        ] {
+            let players: Vec<Box<dyn PlayerBehavior>> = pieces.iter()
+                .enumerate()
+                .map(|(id, &piece)| Box::new(RandomPlayer::new(id, piece)) as Box<dyn PlayerBehavior>)
+                .collect();
+            let mut game = Game::with_players(9, rules, players);
+            for action in [(4, 4), (4, 5), (5, 5)] {
+                game.step(action).unwrap();
+            }
+            if game.phase == Phase::SwapChoice {
+                game.step(Action::Swap).unwrap();
+            }
+            game.step((0, 0)).unwrap();
+            game.save(path).unwrap();
+            let loaded = Game::load(path).unwrap();
+            assert_eq!(loaded.board, game.board);
+            assert_eq!(loaded.pieces(), game.pieces());
+        }
+
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes[0] = 99;
+        std::fs::write(path, &bytes).unwrap();
+        let result = Game::load(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(result, Err(GameFileError::VersionMismatch { found: 99, expected: SAVE_FORMAT_VERSION })));
+    }
+
+    #[test]
+    fn test_mixed_players() {
+        let players: Vec<Box<dyn PlayerBehavior>> = vec![
+            Box::new(MCTSPlayer::new(7, Piece::Black, 50, DEFAULT_EXPLORATION)),
+            Box::new(RandomPlayer::new(3, Piece::White)),
+        ];
+        let mut game = Game::with_players(7, RuleSet::default(), players);
+        let (_, _, done, outcome) = game.run(false).unwrap();
+        assert!(done);
+        assert!(outcome.is_draw || outcome.winner.is_some());
+        assert_eq!(game.history.iter().map(|m| m.player_idx).take(2).collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "every player needs a color of their own")]
+    fn test_players_need_distinct_colors() {
+        let players: Vec<Box<dyn PlayerBehavior>> = vec![
+            Box::new(RandomPlayer::new(0, Piece::Black)),
+            Box::new(RandomPlayer::new(1, Piece::Black)),
+        ];
+        Game::with_players(7, RuleSet::default(), players);
+    }
+
+    #[test]
+    #[should_panic(expected = "swap openings are played with Black and White")]
+    fn test_swap_openings_need_black_and_white() {
+        let players: Vec<Box<dyn PlayerBehavior>> = vec![
+            Box::new(RandomPlayer::new(0, Piece::Red)),
+            Box::new(RandomPlayer::new(1, Piece::Green)),
+        ];
+        Game::with_players(7, RuleSet::d_pente(), players);
+    }
+
+    #[test]
+    fn test_multiplayer_turns_and_captures() {
+        let mut game = Game::new(9, 3);
+        // Red sandwiches White's pair, then Black's pair
+        for action in [(0, 0), (4, 2), (4, 1), (6, 1), (4, 3), (4, 4)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.captures, vec![0, 0, 2]);
+        assert!(game.board.is_empty(4, 2) && game.board.is_empty(4, 3));
+        for action in [(6, 2), (8, 8), (6, 0), (0, 4), (8, 6), (6, 3)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.captures, vec![0, 0, 4]);
+        assert!(game.board.is_empty(6, 1) && game.board.is_empty(6, 2));
+
+        let mut game = Game::new(9, 4);
+        let (_, reward, done, outcome) = game.run(true).unwrap();
+        assert!(done);
+        let order: Vec<_> = game.moves().take(5).map(|m| m.player_idx).collect();
+        assert_eq!(order, vec![0, 1, 2, 3, 0]);
+        if let Some(winner) = outcome.winner {
+            assert_eq!(outcome.rewards.iter().filter(|&&r| r == 1.0).count(), 1);
+            assert_eq!(outcome.rewards[winner], 1.0);
+            assert_eq!(reward, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_team_pente() {
+        let mut game = Game::with_rules(9, 4, RuleSet::team_pente());
+        // Black flanks Red's pair, but partners can't capture each other
+        for action in [(4, 0), (0, 0), (4, 1), (0, 8), (8, 0), (8, 8), (4, 2), (0, 4), (4, 3)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.board.get(4, 1), Piece::Red);
+        assert_eq!(game.captures, vec![0, 0, 0, 0]);
+
+        game.captures = vec![2, 0, 2, 0];
+        assert_eq!(game.side_captures(0), 4);
+        assert_eq!(game.side_captures(3), 0);
+
+        // A line by Red wins for Black too
+        game.step((8, 4)).unwrap();
+        for col in 0..4 {
+            game.board.set(6, col, Piece::Red);
+        }
+        let (_, reward, done, outcome) = game.step((6, 4)).unwrap();
+        assert!(done);
+        assert_eq!(reward, 1.0);
+        assert_eq!(outcome.winner, Some(2));
+        assert_eq!(outcome.rewards, vec![1.0, -1.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_keryo_captures() {
+        let mut game = Game::with_rules(9, 2, RuleSet::keryo());
+        // Black at (2, 0) and (2, 4) sandwiches three White stones
+        for action in [(2, 0), (2, 1), (8, 8), (2, 2), (8, 6), (2, 3), (2, 4)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.captures, vec![3, 0]);
+        assert!((1..4).all(|col| game.board.is_empty(2, col)));
+
+        // Plain Pente leaves the triple alone
+        let mut game = Game::new(9, 2);
+        for action in [(2, 0), (2, 1), (8, 8), (2, 2), (8, 6), (2, 3), (2, 4)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.captures, vec![0, 0]);
+
+        let mut game = Game::with_rules(9, 2, RuleSet::keryo());
+        game.captures = vec![13, 0];
+        for action in [(2, 0), (2, 1), (8, 8), (2, 2)] {
+            game.step(action).unwrap();
+        }
+        let (_, _, done, outcome) = game.step((2, 3)).unwrap();
+        assert!(done);
+        assert_eq!(outcome.winner, Some(0));
+    }
+
+    #[test]
+    fn test_gomoku_and_renju() {
+        // No captures in Gomoku
+        let mut game = Game::with_rules(9, 2, RuleSet::gomoku());
+        for action in [(4, 1), (4, 2), (0, 0), (4, 3), (4, 4)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.captures, vec![0, 0]);
+        assert_eq!(game.board.get(4, 2), Piece::White);
+
+        // Black may not play a double three in Renju, White may
+        let mut game = Game::with_rules(15, 2, RuleSet::renju());
+        for action in [(7, 7), (0, 0), (7, 8), (0, 2), (5, 9), (0, 4), (6, 9), (0, 6)] {
+            game.step(action).unwrap();
+        }
+        assert!(!game.legal_moves().contains(&(7, 9)));
+        assert_eq!(game.step((7, 9)).err(), Some(MoveError::Forbidden));
+        assert!(game.board.is_empty(7, 9));
+        game.player_idx = 1;
+        assert!(game.legal_moves().contains(&(7, 9)));
+
+        // An overline doesn't win for Black, but does for White
+        let rules = RuleSet::renju();
+        assert!(!rules.is_winning_line(Piece::Black, 6));
+        assert!(rules.is_winning_line(Piece::Black, 5));
+        assert!(rules.is_winning_line(Piece::White, 6));
+
+        // A five wins for Black even when the same stone also makes an overline
+        let mut game = Game::with_rules(15, 2, RuleSet::renju());
+        for col in [2, 3, 4, 6, 7] {
+            game.board.set(7, col, Piece::Black);
+        }
+        for row in [5, 6, 8, 9] {
+            game.board.set(row, 5, Piece::Black);
+        }
+        game.turn = 10;
+        assert!(game.legal_moves().contains(&(7, 5)));
+        let (_, reward, done, outcome) = game.step((7, 5)).unwrap();
+        assert!(done);
+        assert_eq!(reward, 1.0);
+        assert_eq!(outcome.winning_line, Some((5..10).map(|row| (row, 5)).collect()));
+    }
+
+    #[test]
+    fn test_tournament_opening() {
+        let mut game = Game::with_rules(19, 2, RuleSet::tournament_pente());
+        assert_eq!(game.legal_moves(), vec![(9, 9)]);
+        assert_eq!(game.action_mask().iter().filter(|&&legal| legal).count(), 1);
+        assert!(game.action_mask()[9 * 19 + 9]);
+
+        game.step((9, 9)).unwrap();
+        assert_eq!(game.legal_moves().len(), 360);
+        game.step((10, 10)).unwrap();
+        // The 5x5 square around the center is closed for the first player's second stone
+        let moves = game.legal_moves();
+        assert_eq!(moves.len(), 361 - 25);
+        assert!(!moves.contains(&(11, 7)) && moves.contains(&(12, 9)));
+        assert_eq!(game.step((8, 8)).err(), Some(MoveError::Forbidden));
+        assert!(game.board.is_empty(8, 8));
+        game.step((6, 9)).unwrap();
+        assert_eq!(game.legal_moves().len(), 358);
+    }
+
+    #[test]
+    fn test_swap_openings() {
+        let rules = RuleSet { protocol: Protocol::Swap, ..RuleSet::gomoku() };
+        let mut game = Game::with_rules(15, 2, rules);
+        // The first player places black, white, black
+        for action in [(7, 7), (7, 8), (8, 8)] {
+            assert_eq!(game.player_idx, 0);
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.board.get(7, 8), Piece::White);
+        assert_eq!((game.phase, game.player_idx), (Phase::SwapChoice, 1));
+        assert!(game.legal_moves().is_empty());
+        assert_eq!(game.legal_actions(), vec![Action::Swap, Action::Stay]);
+        assert_eq!(game.action_mask().iter().filter(|&&legal| legal).count(), 2);
+        assert_eq!(game.make_move(Action::PlaceTwo).err(), Some(MoveError::WrongPhase));
+
+        // The second player takes Black, so the first continues with White
+        game.step(Action::Swap).unwrap();
+        assert_eq!(game.players[1].get_piece(), Piece::Black);
+        assert_eq!((game.phase, game.player_idx), (Phase::Play, 0));
+        game.step((0, 0)).unwrap();
+        assert_eq!(game.board.get(0, 0), Piece::White);
+        assert_eq!(game.player_idx, 1);
+
+        while game.undo().is_some() {}
+        assert_eq!(game.players[0].get_piece(), Piece::Black);
+        assert_eq!((game.phase, game.player_idx, game.turn), (Phase::Placing { until: 3 }, 0, 0));
+
+        let rules = RuleSet { protocol: Protocol::Swap2, ..RuleSet::gomoku() };
+        let mut game = Game::with_rules(15, 2, rules);
+        for action in [(7, 7), (7, 8), (8, 8)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.legal_actions().len(), 3);
+        // The second player adds white and black, then the first player chooses
+        game.step(Action::PlaceTwo).unwrap();
+        game.step((6, 6)).unwrap();
+        game.step((9, 9)).unwrap();
+        assert_eq!((game.board.get(6, 6), game.board.get(9, 9)), (Piece::White, Piece::Black));
+        assert_eq!((game.phase, game.player_idx), (Phase::SwapChoice, 0));
+        game.step(Action::Stay).unwrap();
+        assert_eq!((game.phase, game.player_idx), (Phase::Play, 1));
+
+        let replayed = Game::replay(&game.board, &game.starting_pieces(), game.rules.clone(), game.moves()).unwrap();
+        assert_eq!(replayed.board, game.board);
+        assert_eq!((replayed.phase, replayed.player_idx), (game.phase, game.player_idx));
+        let (_, _, done, _) = game.run(true).unwrap();
+        assert!(done);
+    }
+
+    #[test]
+    fn test_d_pente_opening() {
+        let mut game = Game::with_rules(19, 2, RuleSet::d_pente());
+        for action in [(9, 9), (9, 10), (8, 8), (10, 10)] {
+            assert_eq!(game.player_idx, 0);
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.board.get(10, 10), Piece::White);
+        assert_eq!((game.phase, game.player_idx), (Phase::ColorChoice, 1));
+        assert!(game.make_move(Action::Swap).is_err());
+        assert!(game.make_move(Action::ChooseColor(Piece::Red)).is_err());
+
+        // Black has the fifth stone, so keeping White lets the first player move
+        game.step(Action::ChooseColor(Piece::White)).unwrap();
+        assert!(!game.history.last().unwrap().swapped);
+        assert_eq!((game.phase, game.player_idx), (Phase::Play, 0));
+        game.undo();
+
+        game.step(Action::ChooseColor(Piece::Black)).unwrap();
+        assert_eq!(game.players[0].get_piece(), Piece::White);
+        assert_eq!(game.player_idx, 1);
+        game.undo();
+        assert_eq!(game.players[1].get_piece(), Piece::White);
+
+        // DK-Pente combines the opening with Keryo captures
+        let rules = RuleSet::dk_pente();
+        assert_eq!(rules.capture_sizes, 2..=3);
+        let mut game = Game::with_rules(9, 2, rules);
+        assert_eq!(game.phase, Phase::Placing { until: 4 });
+        let (_, _, done, _) = game.run(true).unwrap();
+        assert!(done);
+    }
+
+    #[test]
+    fn test_boat_pente() {
+        let mut game = Game::with_rules(9, 2, RuleSet::boat_pente());
+        for col in 0..4 {
+            game.board.set(4, col, Piece::Black);
+        }
+        // White at (2, 1) can take the pair (3, 1), (4, 1) out of the line with (5, 1)
+        game.board.set(3, 1, Piece::Black);
+        game.board.set(2, 1, Piece::White);
+        let (_, _, done, _) = game.step((4, 4)).unwrap();
+        assert!(!done);
+
+        let (_, _, done, _) = game.step((5, 1)).unwrap();
+        assert!(!done);
+        assert_eq!(game.captures, vec![0, 2]);
+        assert!(game.board.is_empty(4, 1));
+
+        // Left standing, the line wins once White has replied
+        game.undo();
+        let (_, reward, done, outcome) = game.step((8, 8)).unwrap();
+        assert!(done);
+        assert_eq!(reward, -1.0);
+        assert_eq!(outcome.winner, Some(0));
+
+        // A line nobody can break wins at once
+        game.undo();
+        game.undo();
+        game.board.set(2, 1, Piece::Empty);
+        let (_, _, done, outcome) = game.step((4, 4)).unwrap();
+        assert!(done);
+        assert_eq!(outcome.winner, Some(0));
+
+        // A reply that reaches the capture threshold wins, even without breaking the line
+        game.undo();
+        game.board.set(2, 1, Piece::White);
+        for col in [6, 7] {
+            game.board.set(7, col, Piece::Black);
+        }
+        game.board.set(7, 5, Piece::White);
+        game.captures = vec![0, 8];
+        let (_, _, done, _) = game.step((4, 4)).unwrap();
+        assert!(!done);
+        let (_, reward, done, outcome) = game.step((7, 8)).unwrap();
+        assert!(done);
+        assert_eq!(game.captures, vec![0, 10]);
+        assert_eq!(reward, 1.0);
+        assert_eq!((outcome.winner, outcome.winning_line), (Some(1), None));
+    }
+
+    #[test]
+    fn test_poof_pente() {
+        let mut game = Game::with_rules(9, 2, RuleSet::poof_pente());
+        // Black fills the gap of W B _ W and loses the pair to White
+        for action in [(4, 2), (4, 1), (0, 0), (4, 4)] {
+            game.step(action).unwrap();
+        }
+        let before = game.board.clone();
+        game.step((4, 3)).unwrap();
+        assert!(game.board.is_empty(4, 2) && game.board.is_empty(4, 3));
+        assert_eq!(game.captures, vec![0, 2]);
+        let record = game.history.last().unwrap();
+        assert!(record.captured.is_empty());
+        assert_eq!(record.poofed, vec![(1, vec![((4, 2), Piece::Black), ((4, 3), Piece::Black)])]);
+
+        game.undo();
+        assert_eq!(game.board, before);
+        assert_eq!(game.captures, vec![0, 0]);
+
+        // Poofed stones can win the game for the opponent
+        game.captures = vec![0, 8];
+        let (_, reward, done, outcome) = game.step((4, 3)).unwrap();
+        assert!(done);
+        assert_eq!(reward, -1.0);
+        assert_eq!(outcome.winner, Some(1));
+
+        // A capture that reaches the threshold wins even if the capturing stone is
+        // poofed and the opponent reaches it too
+        let mut game = Game::with_rules(9, 2, RuleSet::poof_pente());
+        // White at (4, 4) takes the pair at (4, 2) and (4, 3), then loses itself
+        // and (5, 4) between the black stones at (3, 4) and (6, 4)
+        for (x, y) in [(4, 1), (5, 4)] {
+            game.board.set(x, y, Piece::White);
+        }
+        for (x, y) in [(4, 2), (4, 3), (3, 4), (6, 4)] {
+            game.board.set(x, y, Piece::Black);
+        }
+        game.captures = vec![8, 8];
+        game.player_idx = 1;
+        let (_, reward, done, outcome) = game.step((4, 4)).unwrap();
+        assert!(game.board.is_empty(4, 4) && game.board.is_empty(5, 4));
+        assert_eq!(game.captures, vec![10, 10]);
+        assert!(done);
+        assert_eq!(reward, 1.0);
+        assert_eq!(outcome.winner, Some(1));
+
+        // Plain Pente has no suicide captures
+        let mut game = Game::new(9, 2);
+        for action in [(4, 2), (4, 1), (0, 0), (4, 4), (4, 3)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.board.get(4, 3), Piece::Black);
+    }
+
+    #[test]
+    fn test_connect6_turns() {
+        let mut game = Game::with_rules(19, 2, RuleSet::connect6());
+        assert_eq!(game.stones_left_in_turn(), 1);
+        game.step((9, 9)).unwrap();
+        assert_eq!((game.player_idx, game.stones_left_in_turn()), (1, 2));
+        game.step((0, 0)).unwrap();
+        assert_eq!((game.player_idx, game.stones_left_in_turn()), (1, 1));
+        game.step((0, 1)).unwrap();
+        assert_eq!((game.player_idx, game.stones_left_in_turn()), (0, 2));
+
+        game.undo();
+        assert_eq!((game.player_idx, game.stones_left_in_turn()), (1, 1));
+
+        // Five in a row isn't enough, six is
+        game.step((0, 1)).unwrap();
+        for col in 10..12 {
+            game.step((9, col)).unwrap();
+        }
+        game.step((0, 2)).unwrap();
+        game.step((0, 3)).unwrap();
+        game.step((9, 12)).unwrap();
+        let (_, _, done, _) = game.step((9, 13)).unwrap();
+        assert!(!done);
+        game.step((0, 4)).unwrap();
+        game.step((5, 5)).unwrap();
+        let (_, reward, done, outcome) = game.step((9, 14)).unwrap();
+        assert!(done);
+        assert_eq!(reward, 1.0);
+        assert_eq!(outcome.winner, Some(0));
+        assert_eq!(game.history.iter().filter(|m| m.player_idx == 0).count(), 6);
+    }
+
+    #[test]
+    fn test_rectangular_game() {
+        let players: Vec<Box<dyn PlayerBehavior>> = vec![
+            Box::new(RandomPlayer::new(0, Piece::Black)),
+            Box::new(RandomPlayer::new(1, Piece::White)),
+        ];
+        let mut game = Game::on_board(Board::rectangular(5, 25), RuleSet::default(), players);
+        assert_eq!(game.action_mask().len(), 5 * 25 + crate::action::CHOICES.len());
+        game.step((4, 24)).unwrap();
+        assert_eq!(game.make_move((5, 0)).err(), Some(MoveError::OutOfBounds));
+        assert_eq!(game.make_move((0, 25)).err(), Some(MoveError::OutOfBounds));
+        let (_, _, done, _) = game.run(false).unwrap();
+        assert!(done);
+
+        let replayed = Game::replay(&game.board, &game.starting_pieces(), game.rules.clone(), game.moves()).unwrap();
+        assert_eq!(replayed.board, game.board);
+    }
+
+    #[test]
+    fn test_torus_game() {
+        let players: Vec<Box<dyn PlayerBehavior>> = vec![
+            Box::new(RandomPlayer::new(0, Piece::Black)),
+            Box::new(RandomPlayer::new(1, Piece::White)),
+        ];
+        let mut game = Game::on_board(Board::toroidal(9, 9), RuleSet::default(), players);
+        for (col, row) in [7, 8, 0, 1].into_iter().zip(0..) {
+            game.step((4, col)).unwrap();
+            game.step((row, 4)).unwrap();
+        }
+        let (_, _, done, outcome) = game.step((4, 2)).unwrap();
+        assert!(done);
+        assert_eq!(outcome.winning_line.map(|line| line.len()), Some(5));
+
+        game.undo();
+        game.run(false).unwrap();
+        let replayed = Game::replay(&game.board, &game.starting_pieces(), game.rules.clone(), game.moves()).unwrap();
+        assert_eq!(replayed.board, game.board);
+    }
+
+    #[test]
+    fn test_quiet_moves_draw() {
+        let rules = RuleSet { quiet_moves_to_draw: Some(5), ..RuleSet::pente() };
+        let mut game = Game::with_rules(9, 2, rules);
+        // Scattered stones and twos make no threats
+        for action in [(0, 0), (0, 4), (0, 1), (8, 8)] {
+            game.step(action).unwrap();
+        }
+        assert_eq!(game.quiet_moves, 4);
+
+        // A three is a threat and starts the count again
+        game.step((0, 2)).unwrap();
+        assert_eq!(game.quiet_moves, 0);
+        game.step((8, 6)).unwrap();
+        assert_eq!(game.quiet_moves, 1);
+        game.undo();
+        game.undo();
+        assert_eq!(game.quiet_moves, 4);
+
+        let (_, _, done, outcome) = game.step((4, 4)).unwrap();
+        assert!(done);
+        assert_eq!(outcome.draw_reason, Some(DrawReason::NoThreats));
+
+        // Filling the board is a draw of its own
+        let mut game = Game::with_rules(2, 2, RuleSet::gomoku());
+        let (_, _, done, outcome) = game.run(true).unwrap();
+        assert!(done);
+        assert_eq!(outcome.draw_reason, Some(DrawReason::BoardFull));
+    }
+
+    #[test]
+    fn test_hash() {
+        let mut game = Game::new(9, 2);
+        let start = game.hash();
+        let mut hashes = vec![start];
+        // The last move captures the white pair at (4, 2) and (4, 3)
+        for action in [(4, 1), (4, 2), (0, 0), (4, 3), (4, 4)] {
+            game.step(action).unwrap();
+            assert!(!hashes.contains(&game.hash()));
+            hashes.push(game.hash());
+        }
+        while game.undo().is_some() {
+            hashes.pop();
+            assert_eq!(game.hash(), *hashes.last().unwrap());
+        }
+        assert_eq!(game.hash(), start);
+
+        // Stones alone don't decide the hash: the side to move and captures count too
+        let mut other = Game::new(9, 2);
+        for action in [(0, 0), (4, 2), (4, 1), (4, 3), (4, 4)] {
+            other.step(action).unwrap();
+        }
+        let mut game = Game::new(9, 2);
+        for action in [(4, 1), (0, 1), (0, 0), (1, 1), (4, 4)] {
+            game.step(action).unwrap();
+        }
+        game.board.set(0, 1, Piece::Empty);
+        game.board.set(1, 1, Piece::Empty);
+        assert_eq!(game.board, other.board);
+        assert_ne!(game.hash(), other.hash());
+        game.captures[0] = 2;
+        assert_eq!(game.hash(), other.hash());
+        game.player_idx = 0;
+        assert_ne!(game.hash(), other.hash());
+
+        // Settling the colors ends the swap phase, and undoing the choice restores it
+        let rules = RuleSet { protocol: Protocol::Swap, ..RuleSet::pente() };
+        let mut game = Game::with_rules(9, 2, rules);
+        for action in [(4, 4), (4, 5), (5, 5)] {
+            game.step(action).unwrap();
+        }
+        let before = game.hash();
+        game.step(Action::Swap).unwrap();
+        let swapped = game.hash();
+        assert_ne!(swapped, before);
+        game.undo();
+        assert_eq!(game.hash(), before);
+        // Whoever takes Black, the same stones with White to move hash alike
+        game.step(Action::Stay).unwrap();
+        assert_eq!(game.hash(), swapped);
+
+        // Rotated and reflected positions share a canonical hash
+        let mut game = Game::new(9, 2);
+        let mut mirrored = Game::new(9, 2);
+        for (row, col) in [(1, 2), (4, 4), (0, 7)] {
+            game.step((row, col)).unwrap();
+            mirrored.step((col, row)).unwrap();
+        }
+        assert_ne!(game.hash(), mirrored.hash());
+        assert_eq!(game.canonical_hash(), mirrored.canonical_hash());
+        mirrored.undo();
+        assert_ne!(game.canonical_hash(), mirrored.canonical_hash());
+    }
+
+    #[test]
+    fn test_move_errors() {
+        let mut game = Game::new(9, 2);
+        game.step((4, 4)).unwrap();
+        assert_eq!(game.step((4, 4)).err(), Some(MoveError::Occupied));
+        assert_eq!(game.step((9, 0)).err(), Some(MoveError::OutOfBounds));
+        assert_eq!(game.step(Action::Swap).err(), Some(MoveError::WrongPhase));
+        // Refused actions leave the game as it was
+        assert_eq!((game.turn, game.player_idx, game.history.len()), (1, 1, 1));
+
+        for action in [(0, 0), (4, 5), (0, 2), (4, 6), (0, 4), (4, 7), (0, 6)] {
+            game.step(action).unwrap();
+        }
+        let (_, _, done, _) = game.step((4, 8)).unwrap();
+        assert!(done && game.is_over);
+        assert!(game.legal_actions().is_empty());
+        assert_eq!(game.step((8, 8)).err(), Some(MoveError::GameOver));
+
+        game.undo();
+        assert!(!game.is_over);
+        assert!(game.step((8, 8)).is_ok());
+    }
+}
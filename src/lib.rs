@@ -0,0 +1,10 @@
+pub mod action;
+pub mod board;
+pub mod rules;
+pub mod renju;
+pub mod random_player;
+pub mod mcts_player;
+pub mod game;
+pub mod player;
+pub mod symmetry;
+pub mod zobrist;
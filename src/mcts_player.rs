@@ -1,113 +1,167 @@
-use crate::{board::{Board, Piece}, game::Game};
+use rand::Rng;
+
+use crate::{action::Action, board::Piece, game::Game};
 
 // Implement player that performs MCTS rollout
 
+pub const DEFAULT_ITERATIONS: usize = 1000;
+pub const DEFAULT_EXPLORATION: f32 = std::f32::consts::SQRT_2;
+
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct MCTSPlayer {
     pub piece_type: Piece,
     pub id: usize,
-    pub num_rollouts: usize,
-    pub captured_pairs: usize,
+    // Number of select/expand/playout/backpropagate passes per move
+    pub iterations: usize,
+    // Exploration constant c in UCB1 = wins / visits + c * sqrt(ln(parent visits) / visits)
+    pub exploration: f32,
     }
 
-impl MCTSPlayer {
-    pub fn new(id: usize, piece_type: Piece, num_rollouts: usize, captured_pairs: usize) -> MCTSPlayer {
-        MCTSPlayer { id, piece_type, num_rollouts, captured_pairs }
+// A node of the search tree, reached by playing `action` from its parent
+struct Node {
+    action: Action,
+    // Index of the player who played `action`
+    player: usize,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Action>,
+    visits: f32,
+    wins: f32,
+    // Rewards of every player, set once the game is known to be over at this node
+    terminal: Option<Vec<f32>>,
+}
+
+impl Node {
+    fn new(action: Action, player: usize, parent: Option<usize>, game: &Game) -> Node {
+        Node {
+            action,
+            player,
+            parent,
+            children: Vec::new(),
+            untried: game.legal_actions(),
+            visits: 0.0,
+            wins: 0.0,
+            terminal: None,
+        }
     }
 
-    pub fn act(&mut self, board: &mut Board, x: usize, y: usize) -> Result<(), String> {
-        if x >= board.size || y >= board.size {
-            return Err("Position out of bounds".to_string());
-        }
-        if board.grid[[x, y]] != Piece::Empty {
-            return Err("Position already occupied".to_string());
-        }
-        board.grid[[x, y]] = self.piece_type.clone();
-        // Capture logic
-        self.capture(board, x, y);
+    fn ucb1(&self, parent_visits: f32, exploration: f32) -> f32 {
+        self.wins / self.visits + exploration * (parent_visits.ln() / self.visits).sqrt()
+    }
+}
+
+// Map a game reward in [-1, 1] to a UCB1 score in [0, 1], so draws count half
+fn score(reward: f32) -> f32 {
+    (reward + 1.0) / 2.0
+}
 
-        Ok(())
+impl MCTSPlayer {
+    pub fn new(id: usize, piece_type: Piece, iterations: usize, exploration: f32) -> MCTSPlayer {
+        MCTSPlayer { id, piece_type, iterations, exploration }
     }
 
-    // Define think function that performs MCTS rollout
-    pub fn think(&self, mut game: Game) -> (usize, usize) {
-        // Choose random unoccupied position
-        let mut valid_actions = game.board.get_moves();
-
-        // For every valid action, perform a rollou
-        let mut best_score = 0.0;
-        let mut best_action = valid_actions[0];
-
-        for (i, action) in valid_actions.iter().enumerate() {
-            let mut total_score: f32 = 0.0;
-            let (winner_0_count, winner_1_count, is_draw_count) = game.rollout(self.num_rollouts);
-            // println!("{} {} {}", winner_0_count, winner_1_count, is_draw_count);
-            let mut win_diff = winner_0_count as f32 - winner_1_count as f32;
-            if self.id == 0 {
-                total_score = (win_diff) / (self.num_rollouts as f32);
-            } else if self.id == 1 {
-                total_score = (-win_diff) / (self.num_rollouts as f32);
+    // Define think function that performs UCT Monte Carlo tree search and
+    // returns the most visited action at the root
+    pub fn think(&self, game: &Game) -> Action {
+        assert!(!game.is_over, "MCTSPlayer::think called on a finished game");
+        let mut game = game.clone();
+        let mut rng = rand::thread_rng();
+        let root_len = game.history.len();
+        let num_players = game.players.len();
+        let last_player = (game.player_idx + num_players - 1) % num_players;
+        let mut tree = vec![Node::new(Action::Place(0, 0), last_player, None, &game)];
+
+        for _ in 0..self.iterations {
+            let mut node = 0;
+
+            // 1. Selection: follow UCB1 through fully expanded nodes
+            while tree[node].terminal.is_none() && tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                let parent_visits = tree[node].visits;
+                node = *tree[node].children.iter()
+                    .max_by(|&&a, &&b| {
+                        let a = tree[a].ucb1(parent_visits, self.exploration);
+                        let b = tree[b].ucb1(parent_visits, self.exploration);
+                        a.total_cmp(&b)
+                    })
+                    .unwrap();
+                game.step(tree[node].action).expect("tree actions are legal");
             }
-            if total_score > best_score {
-                // println!("{} {}", total_score, best_score);
-                best_score = total_score;
-                best_action = *action;
+
+            // 2. Expansion: add one untried move as a new child
+            if tree[node].terminal.is_none() && !tree[node].untried.is_empty() {
+                let i = rng.gen_range(0..tree[node].untried.len());
+                let action = tree[node].untried.swap_remove(i);
+                let player = game.player_idx;
+                let (_, _, done, outcome) = game.step(action).expect("untried actions are legal");
+                let mut child = Node::new(action, player, Some(node), &game);
+                if done {
+                    child.terminal = Some(outcome.rewards);
+                }
+                tree.push(child);
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                node = child;
+            }
+
+            // 3. Playout: finish the game with uniformly random moves
+            let rewards = match &tree[node].terminal {
+                Some(rewards) => rewards.clone(),
+                None => loop {
+                    let valid_actions = game.legal_actions();
+                    if valid_actions.is_empty() {
+                        break vec![0.0; num_players];
+                    }
+                    let action = valid_actions[rng.gen_range(0..valid_actions.len())];
+                    let (_, _, done, outcome) = game.step(action).expect("legal actions can be played");
+                    if done {
+                        break outcome.rewards;
+                    }
+                },
+            };
+
+            // 4. Backpropagation: credit each node to the player who moved into it
+            let mut current = Some(node);
+            while let Some(n) = current {
+                tree[n].visits += 1.0;
+                tree[n].wins += score(rewards[tree[n].player]);
+                current = tree[n].parent;
+            }
+
+            while game.history.len() > root_len {
+                game.undo();
             }
         }
-        // println!("Finished thinking for turn {}", game.turn);
-        return best_action;
-    }
 
-    pub fn owns_piece(&self, board: &Board, x: usize, y: usize) -> bool {
-        board.grid[[x, y]] == self.piece_type
+        tree[0].children.iter()
+            .max_by(|&&a, &&b| tree[a].visits.total_cmp(&tree[b].visits))
+            .map(|&child| tree[child].action)
+            .or_else(|| game.legal_actions().first().copied())
+            .expect("the player to move has a legal action")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_think_finds_winning_move() {
+        let mut game = Game::new(7, 2);
+        // Black has four in a row blocked on the left, only (3, 5) completes five
+        for action in [(3, 1), (3, 0), (3, 2), (0, 6), (3, 3), (6, 0), (3, 4), (6, 6)] {
+            game.step(action).unwrap();
+        }
+        let player = MCTSPlayer::new(0, Piece::Black, 2000, DEFAULT_EXPLORATION);
+        assert_eq!(player.think(&game), Action::Place(3, 5));
     }
 
-    pub fn capture(&mut self, board: &mut Board, x: usize, y: usize) {
-        let movement_vectors: [(isize, isize); 8] = [
-            (0, 1), (0, -1), (1, 0), (-1, 0),
-            (1, 1), (-1, -1), (1, -1), (-1, 1),
-        ];
-
-        let x_isize = x as isize;  // Convert to isize for safe arithmetic
-        let y_isize = y as isize;
-
-        for &movement_vector in &movement_vectors {
-            let first_x = x_isize.checked_add(movement_vector.0);
-            let first_y = y_isize.checked_add(movement_vector.1);
-            let second_x = x_isize.checked_add(2 * movement_vector.0);
-            let second_y = y_isize.checked_add(2 * movement_vector.1);
-            let pair_x = x_isize.checked_add(3 * movement_vector.0);  // Position to check for a matching piece
-            let pair_y = y_isize.checked_add(3 * movement_vector.1);
-
-            if let (Some(first_x), Some(first_y), Some(pair_x), Some(pair_y), Some(second_x), Some(second_y)) = (first_x, first_y, pair_x, pair_y, second_x, second_y) {
-                if pair_x >= 0 && pair_y >= 0 &&
-                    (pair_x as usize) < board.size && 
-                    (pair_y as usize) < board.size {
-
-                    let first_x = first_x as usize;
-                    let first_y = first_y as usize;
-                    let second_x = second_x as usize;
-                    let second_y = second_y as usize;
-                    let pair_x = pair_x as usize;
-                    let pair_y = pair_y as usize;
-
-                    // Capture logic
-                    if self.owns_piece(board, pair_x, pair_y) && // Check if player owns the piece at the pair position
-                        board.grid[[first_x, first_y]] != Piece::Empty && // Make sure it's not empty
-                        board.grid[[second_x, second_y]] != Piece::Empty && // Capture the next piece
-                        board.grid[[first_x, first_y]] != self.piece_type && // Check if the next piece is an opponent's piece
-                        board.grid[[second_x, second_y]] == board.grid[[first_x, first_y]] {
-
-                        // Assuming a capture scenario - sandwiching one piece
-                        self.captured_pairs += 1; // Increment captured pairs
-                        // Clear the captured piece(s) from the board
-                        board.grid[[first_x, first_y]] = Piece::Empty; // Capture the next piece
-                        board.grid[[second_x, second_y]] = Piece::Empty; // Capture the next piece
-
-                        // Extend this logic if your game involves capturing multiple pieces per move
-                    }
-                }
-            }
+    #[test]
+    #[should_panic(expected = "MCTSPlayer::think called on a finished game")]
+    fn test_think_on_finished_game() {
+        let mut game = Game::new(7, 2);
+        for action in [(3, 0), (0, 0), (3, 1), (0, 2), (3, 2), (0, 4), (3, 3), (0, 6), (3, 4)] {
+            game.step(action).unwrap();
         }
+        MCTSPlayer::new(1, Piece::White, 10, DEFAULT_EXPLORATION).think(&game);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,141 @@
+use crate::board::{Board, Piece};
+
+// Forbidden-move detection for Renju, where Black may not make a double three,
+// a double four or an overline. A move that makes exactly five is never forbidden.
+//
+// Threes are counted without checking whether the move that would turn them
+// into a straight four is itself forbidden, so a few "false threes" that a
+// tournament referee would allow are reported as real ones.
+
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+// Length of the run of black stones through (x, y) along dir
+fn run_length(board: &Board, x: usize, y: usize, dir: (isize, isize)) -> usize {
+    // Bounded so a line all the way around a torus still ends
+    let extent = |sign: isize| {
+        (1..board.rows.max(board.cols) as isize)
+            .take_while(|&k| board.offset(x, y, dir, sign * k).is_some_and(|(nx, ny)| board.get(nx, ny) == Piece::Black))
+            .count()
+    };
+    1 + extent(1) + extent(-1)
+}
+
+// Empty points along dir where one more black stone makes exactly five through (x, y)
+fn five_points(board: &mut Board, x: usize, y: usize, dir: (isize, isize), win_length: usize) -> Vec<isize> {
+    let reach = win_length as isize - 1;
+    (-reach..=reach)
+        .filter(|&k| {
+            let Some((nx, ny)) = board.offset(x, y, dir, k).filter(|&(nx, ny)| board.is_empty(nx, ny)) else {
+                return false;
+            };
+            board.set(nx, ny, Piece::Black);
+            let is_five = run_length(board, x, y, dir) == win_length;
+            board.set(nx, ny, Piece::Empty);
+            is_five
+        })
+        .collect()
+}
+
+// Number of fours through (x, y) along dir. A straight four, which can be
+// completed at either end, still counts as one.
+fn count_fours(board: &mut Board, x: usize, y: usize, dir: (isize, isize), win_length: usize) -> usize {
+    match five_points(board, x, y, dir, win_length).as_slice() {
+        [a, b] if b - a == win_length as isize => 1,
+        points => points.len(),
+    }
+}
+
+// Check whether one more stone along dir can make a straight four through (x, y)
+fn is_open_three(board: &mut Board, x: usize, y: usize, dir: (isize, isize), win_length: usize) -> bool {
+    let reach = win_length as isize - 1;
+    (-reach..=reach).any(|k| {
+        let Some((nx, ny)) = board.offset(x, y, dir, k).filter(|&(nx, ny)| board.is_empty(nx, ny)) else {
+            return false;
+        };
+        board.set(nx, ny, Piece::Black);
+        let straight = matches!(
+            five_points(board, x, y, dir, win_length).as_slice(),
+            [a, b] if b - a == win_length as isize
+        );
+        board.set(nx, ny, Piece::Empty);
+        straight
+    })
+}
+
+// Check whether Black placing a stone on the empty point (x, y) is forbidden
+pub fn is_forbidden(board: &Board, x: usize, y: usize, win_length: usize) -> bool {
+    // A double three needs at least four other black stones in reach, skip the
+    // full analysis for the vast majority of points that don't have them
+    let reach = win_length as isize;
+    let nearby = DIRECTIONS.iter()
+        .flat_map(|&dir| (-reach..=reach).filter_map(move |k| board.offset(x, y, dir, k)))
+        .filter(|&(nx, ny)| board.get(nx, ny) == Piece::Black)
+        .count();
+    if nearby < win_length - 1 {
+        return false;
+    }
+
+    let mut board = board.clone();
+    board.set(x, y, Piece::Black);
+    let runs = DIRECTIONS.map(|dir| run_length(&board, x, y, dir));
+    if runs.contains(&win_length) {
+        return false;
+    }
+    if runs.iter().any(|&run| run > win_length) {
+        return true;
+    }
+
+    let mut fours = 0;
+    let mut threes = 0;
+    for dir in DIRECTIONS {
+        let line_fours = count_fours(&mut board, x, y, dir, win_length);
+        fours += line_fours;
+        if line_fours == 0 && is_open_three(&mut board, x, y, dir, win_length) {
+            threes += 1;
+        }
+    }
+    fours >= 2 || threes >= 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with(stones: &[(usize, usize)]) -> Board {
+        let mut board = Board::new(15);
+        for &(x, y) in stones {
+            board.set(x, y, Piece::Black);
+        }
+        board
+    }
+
+    #[test]
+    fn test_double_three() {
+        let board = board_with(&[(7, 5), (7, 6), (5, 8), (6, 8)]);
+        assert!(is_forbidden(&board, 7, 8, 5));
+
+        // Blocking the horizontal three leaves only one open three
+        let mut board = board;
+        board.set(7, 4, Piece::White);
+        board.set(7, 9, Piece::White);
+        assert!(!is_forbidden(&board, 7, 8, 5));
+    }
+
+    #[test]
+    fn test_double_four_and_overline() {
+        let mut board = board_with(&[(7, 4), (7, 5), (7, 6), (4, 7), (5, 7), (6, 7)]);
+        board.set(7, 3, Piece::White);
+        board.set(3, 7, Piece::White);
+        assert!(is_forbidden(&board, 7, 7, 5));
+
+        // Two fours in one line: B.BBB.B
+        let board = board_with(&[(3, 3), (3, 5), (3, 7), (3, 9)]);
+        assert!(is_forbidden(&board, 3, 6, 5));
+
+        let board = board_with(&[(2, 0), (2, 1), (2, 2), (2, 4), (2, 5)]);
+        assert!(is_forbidden(&board, 2, 3, 5));
+        // Exactly five is always allowed
+        let board = board_with(&[(2, 0), (2, 1), (2, 2), (2, 4)]);
+        assert!(!is_forbidden(&board, 2, 3, 5));
+    }
+}
@@ -0,0 +1,596 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::board::{Board, CapturedStones, Piece};
+use crate::renju;
+
+// Restrictions on where stones may be placed at the start of the game
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Opening {
+    // Any empty intersection is legal from the first move
+    Free,
+    // The first stone of the game must go on the center point
+    Center,
+    // Tournament Pente: the first player starts on the center point, and their
+    // second stone must be at least three intersections away from it
+    Tournament,
+}
+
+// How the players end up with their colors
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Protocol {
+    // Every player places their own color from the first move
+    Standard,
+    // The first player places three stones (black, white, black), then the
+    // second player chooses which color to play
+    Swap,
+    // Like Swap, but the second player may instead place two more stones (white,
+    // black) and leave the choice of color to the first player
+    Swap2,
+    // D-Pente: the first player places four stones, two of each color, then the
+    // second player picks a side
+    DPente,
+}
+
+// Every parameter of the game that varies between rule variants
+#[derive(Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RuleSet {
+    // Stones in a row needed to win
+    pub win_length: usize,
+    // Whether sandwiched stones are captured at all
+    pub captures: bool,
+    // Captured stones needed to win
+    pub captures_to_win: usize,
+    // Lengths of the runs of stones a sandwich can capture
+    pub capture_sizes: RangeInclusive<usize>,
+    // Whether a line longer than win_length also wins
+    pub overline_wins: bool,
+    // The game is drawn once this many moves have been played
+    pub max_moves: Option<usize>,
+    // Adjudicate a draw once this many moves in a row have gone by without a
+    // threat, meaning a capture or a line of at least win_length - 2 stones
+    pub quiet_moves_to_draw: Option<usize>,
+    pub opening: Opening,
+    // Swap openings only make sense for two players
+    pub protocol: Protocol,
+    // Team Pente: Black and Red play together against White and Green
+    pub teams: bool,
+    // Renju: Black may not make a double three, a double four or an overline,
+    // and only wins with exactly win_length in a row
+    pub renju: bool,
+    // Boat-Pente: a line only wins if the next player can't break it by
+    // capturing stones out of it. A reply that wins by captures still wins.
+    pub boat: bool,
+    // Poof-Pente: a stone that completes a capturable run of its own color
+    // between two enemy stones loses the run to that enemy
+    pub poof: bool,
+    // Stones placed in each turn of the game, the last entry repeating for all
+    // later turns. Connect6 is [1, 2].
+    pub stones_per_turn: Vec<usize>,
+}
+
+// Why an action was refused. The game is left unchanged.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    OutOfBounds,
+    Occupied,
+    // Refused by the opening rule or by a forbidden-move rule such as Renju's
+    Forbidden,
+    // The action doesn't fit the phase, like a stone while a swap choice is pending
+    WrongPhase,
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::OutOfBounds => write!(f, "Position out of bounds"),
+            MoveError::Occupied => write!(f, "Position already occupied"),
+            MoveError::Forbidden => write!(f, "Position not allowed by the rules"),
+            MoveError::WrongPhase => write!(f, "Action not allowed in this phase"),
+            MoveError::GameOver => write!(f, "The game is already over"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+// What placing a stone did to the board
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MoveResult {
+    // Enemy stones removed by the move
+    pub captured: CapturedStones,
+    // Runs of the mover's own stones removed by Poof-Pente, with the color
+    // credited with each
+    pub poofed: Vec<(Piece, CapturedStones)>,
+}
+
+// Why a game ended without a winner
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DrawReason {
+    BoardFull,
+    MoveLimit,
+    // RuleSet::quiet_moves_to_draw moves went by without a threat
+    NoThreats,
+    // The player to move has nowhere to play, like Black when every empty point
+    // is forbidden under Renju, or after the tournament opening on a small board
+    NoLegalMoves,
+}
+
+// Define struct for game outcomes
+pub struct GameOutcome {
+    pub is_game_over: bool,
+    pub is_draw: bool,
+    pub draw_reason: Option<DrawReason>,
+    // Index into Game::players of the winner, if the game has one
+    pub winner: Option<usize>,
+    // Reward of every player: 1 for the winner and any partner, -1 for everyone
+    // else, 0 while the game is running or drawn
+    pub rewards: Vec<f32>,
+    // Stones of the five (or more) in a row that ended the game, if any
+    pub winning_line: Option<Vec<(usize, usize)>>,
+}
+
+impl GameOutcome {
+    pub fn ongoing(num_players: usize) -> GameOutcome {
+        GameOutcome {
+            is_game_over: false,
+            is_draw: false,
+            draw_reason: None,
+            winner: None,
+            rewards: vec![0.0; num_players],
+            winning_line: None,
+        }
+    }
+
+    pub fn draw(num_players: usize, reason: DrawReason) -> GameOutcome {
+        GameOutcome {
+            is_game_over: true,
+            is_draw: true,
+            draw_reason: Some(reason),
+            ..GameOutcome::ongoing(num_players)
+        }
+    }
+
+    pub fn win(winner: usize, rewards: Vec<f32>, winning_line: Option<Vec<(usize, usize)>>) -> GameOutcome {
+        GameOutcome {
+            is_game_over: true,
+            is_draw: false,
+            draw_reason: None,
+            winner: Some(winner),
+            rewards,
+            winning_line,
+        }
+    }
+}
+
+// What the rules look at to decide whether a game is over
+pub struct Position<'a> {
+    pub board: &'a Board,
+    // Color of the player at each seat
+    pub pieces: &'a [Piece],
+    // Captured stones of the player at each seat
+    pub captures: &'a [usize],
+    // Number of stones played so far
+    pub turn: usize,
+    // Stones played in a row without a threat
+    pub quiet_moves: usize,
+    // Color of the next stone, or None while a choice of colors is pending
+    pub next_stone: Option<Piece>,
+    // Point of the stone placed by the move before the last one, if it placed one
+    pub previous_move: Option<(usize, usize)>,
+}
+
+impl Position<'_> {
+    // Seat of the player who plays the given color
+    fn seat_of(&self, piece: Piece) -> usize {
+        self.pieces.iter().position(|&p| p == piece).expect("every stone belongs to a player")
+    }
+}
+
+// Side a color belongs to in team games
+pub fn team(piece: Piece) -> Option<usize> {
+    match piece {
+        Piece::Black | Piece::Red => Some(0),
+        Piece::White | Piece::Green => Some(1),
+        Piece::Empty => None,
+    }
+}
+
+impl RuleSet {
+    // Standard Pente: five in a row or five captured pairs
+    pub fn pente() -> RuleSet {
+        RuleSet {
+            win_length: 5,
+            captures: true,
+            captures_to_win: 10,
+            capture_sizes: 2..=2,
+            overline_wins: true,
+            max_moves: None,
+            quiet_moves_to_draw: None,
+            opening: Opening::Free,
+            protocol: Protocol::Standard,
+            teams: false,
+            renju: false,
+            boat: false,
+            poof: false,
+            stones_per_turn: vec![1],
+        }
+    }
+
+    // Pente with the tournament opening rule
+    pub fn tournament_pente() -> RuleSet {
+        RuleSet { opening: Opening::Tournament, ..RuleSet::pente() }
+    }
+
+    // Keryo-Pente: pairs and triples can be captured, 15 captured stones win
+    pub fn keryo() -> RuleSet {
+        RuleSet { captures_to_win: 15, capture_sizes: 2..=3, ..RuleSet::pente() }
+    }
+
+    // Boat-Pente: Pente where a five has to survive one reply
+    pub fn boat_pente() -> RuleSet {
+        RuleSet { boat: true, ..RuleSet::pente() }
+    }
+
+    // Poof-Pente: Pente with suicide captures
+    pub fn poof_pente() -> RuleSet {
+        RuleSet { poof: true, ..RuleSet::pente() }
+    }
+
+    // D-Pente: Pente started with four stones placed by the first player
+    pub fn d_pente() -> RuleSet {
+        RuleSet { protocol: Protocol::DPente, ..RuleSet::pente() }
+    }
+
+    // DK-Pente: Keryo-Pente with the D-Pente opening
+    pub fn dk_pente() -> RuleSet {
+        RuleSet { protocol: Protocol::DPente, ..RuleSet::keryo() }
+    }
+
+    // Four-player Team Pente, where partners share captures and line wins
+    pub fn team_pente() -> RuleSet {
+        RuleSet { teams: true, ..RuleSet::pente() }
+    }
+
+    // Freestyle Gomoku: no captures, five or more in a row wins. Set
+    // overline_wins to false for the exact-five variant.
+    pub fn gomoku() -> RuleSet {
+        RuleSet { captures: false, ..RuleSet::pente() }
+    }
+
+    // Connect6: six or more in a row wins, each turn after the first places two stones
+    pub fn connect6() -> RuleSet {
+        RuleSet { win_length: 6, stones_per_turn: vec![1, 2], ..RuleSet::gomoku() }
+    }
+
+    // Renju: Gomoku starting on the center point, with Black's forbidden moves
+    pub fn renju() -> RuleSet {
+        RuleSet { opening: Opening::Center, renju: true, ..RuleSet::gomoku() }
+    }
+
+    // Check whether two colors play on the same side. Partners can't capture
+    // each other's stones.
+    pub fn are_partners(&self, a: Piece, b: Piece) -> bool {
+        a == b || (self.teams && team(a).is_some() && team(a) == team(b))
+    }
+
+    // Captured stones of a seat, plus those of its partner in team games
+    pub fn side_captures(&self, pieces: &[Piece], captures: &[usize], seat: usize) -> usize {
+        (0..pieces.len())
+            .filter(|&other| self.are_partners(pieces[seat], pieces[other]))
+            .map(|other| captures[other])
+            .sum()
+    }
+
+    fn win(&self, position: &Position, seat: usize, winning_line: Option<Vec<(usize, usize)>>) -> GameOutcome {
+        let rewards = position.pieces.iter()
+            .map(|&piece| if self.are_partners(position.pieces[seat], piece) { 1.0 } else { -1.0 })
+            .collect();
+        GameOutcome::win(seat, rewards, winning_line)
+    }
+
+    // Decide whether the game is over after the player at seat `mover` placed a
+    // stone on last_move. Every win and draw condition of every variant is here.
+    pub fn outcome(&self, position: &Position, last_move: (usize, usize), mover: usize) -> GameOutcome {
+        let board = position.board;
+        let num_players = position.pieces.len();
+
+        // 1. Boat-Pente: the previous move made a line that could have been broken.
+        //    If the move just played left it standing, the line wins, unless that
+        //    move won the game by captures itself.
+        let mover_captured_enough = self.side_captures(position.pieces, position.captures, mover) >= self.captures_to_win;
+        if let Some((row, col)) = position.previous_move.filter(|_| self.boat && !mover_captured_enough) {
+            if let Some(line) = self.winning_line(board, row, col) {
+                return self.win(position, position.seat_of(board.get(row, col)), Some(line));
+            }
+        }
+
+        // 2. Check the lines through the last stone for a winning run of its color.
+        //    Captures only remove stones, so no other line can have been completed.
+        //    The stone may be gone already if it was poofed.
+        let (row, col) = last_move;
+        if let Some(line) = self.winning_line(board, row, col) {
+            let piece = board.get(row, col);
+            // Under Boat-Pente a line that can be broken is only checked again after the reply
+            if !(self.boat && self.can_break(position, piece, &line)) {
+                return self.win(position, position.seat_of(piece), Some(line));
+            }
+        }
+
+        // 3. Check if the mover (and partner) captured enough of other players pieces.
+        //    Poofed stones are credited to other players, who may win by them too,
+        //    but the mover wins if they reach the threshold in the same move.
+        let capture_winner = (0..num_players)
+            .map(|k| (mover + k) % num_players)
+            .find(|&seat| self.side_captures(position.pieces, position.captures, seat) >= self.captures_to_win);
+        if let Some(seat) = capture_winner {
+            return self.win(position, seat, None);
+        }
+
+        // 4. Check if the board is full or the game is adjudicated a draw
+        if board.is_full() {
+            GameOutcome::draw(num_players, DrawReason::BoardFull)
+        } else if self.is_move_limit_reached(position.turn) {
+            GameOutcome::draw(num_players, DrawReason::MoveLimit)
+        } else if self.quiet_moves_to_draw.is_some_and(|quiet| position.quiet_moves >= quiet) {
+            GameOutcome::draw(num_players, DrawReason::NoThreats)
+        } else if position.next_stone.is_some_and(|piece| !self.can_place(board, position.turn, num_players, piece)) {
+            GameOutcome::draw(num_players, DrawReason::NoLegalMoves)
+        } else {
+            GameOutcome::ongoing(num_players)
+        }
+    }
+
+    // Check whether the next stone can capture stones out of a winning line of
+    // the given color so that it no longer wins
+    fn can_break(&self, position: &Position, piece: Piece, line: &[(usize, usize)]) -> bool {
+        let Some(attacker) = position.next_stone else {
+            return false;
+        };
+        if !self.captures || self.are_partners(attacker, piece) {
+            return false;
+        }
+        let num_players = position.pieces.len();
+        position.board.get_moves().into_iter().any(|(x, y)| {
+            if !self.opening_allows(position.board, position.turn, num_players, x, y) {
+                return false;
+            }
+            let mut board = position.board.clone();
+            let Ok(result) = self.apply_move(&mut board, x, y, attacker) else {
+                return false;
+            };
+            result.captured.iter().any(|(point, _)| line.contains(point))
+                && !line.iter().any(|&(r, c)| board.get(r, c) == piece && self.winning_line(&board, r, c).is_some())
+        })
+    }
+
+    // Check whether a stone of the given color may go anywhere on the board
+    pub fn can_place(&self, board: &Board, turn: usize, num_players: usize, piece: Piece) -> bool {
+        // Any empty point will do unless this stone has points ruled out
+        let restricted = self.opening_restricts(turn, num_players) || (self.renju && piece == Piece::Black);
+        if !restricted {
+            return !board.is_full();
+        }
+        board.get_moves().into_iter().any(|(x, y)| self.allows(board, turn, num_players, piece, x, y))
+    }
+
+    // Check whether the rules let a stone of the given color go on the empty point (x, y)
+    pub fn allows(&self, board: &Board, turn: usize, num_players: usize, piece: Piece, x: usize, y: usize) -> bool {
+        self.opening_allows(board, turn, num_players, x, y) && !self.is_forbidden(board, piece, x, y)
+    }
+
+    // Place a stone of the given color on (x, y) and remove whatever it captures.
+    // Every stone of a game goes through here, whoever chose the move.
+    pub fn apply_move(&self, board: &mut Board, x: usize, y: usize, piece: Piece) -> Result<MoveResult, MoveError> {
+        if !board.contains(x, y) {
+            return Err(MoveError::OutOfBounds);
+        }
+        if !board.is_empty(x, y) {
+            return Err(MoveError::Occupied);
+        }
+        if self.is_forbidden(board, piece, x, y) {
+            return Err(MoveError::Forbidden);
+        }
+        board.set(x, y, piece);
+
+        let mut result = MoveResult::default();
+        if self.captures {
+            let capturable = |victim| !self.are_partners(piece, victim);
+            result.captured = board.capture(x, y, piece, self.capture_sizes.clone(), capturable);
+            if self.poof {
+                result.poofed = board.poof(x, y, self.capture_sizes.clone(), capturable);
+            }
+        }
+        Ok(result)
+    }
+
+    // The run through (x, y) if it wins for the color of the stone there. Every
+    // direction is checked, since an overline in one doesn't spoil a five in another.
+    pub fn winning_line(&self, board: &Board, x: usize, y: usize) -> Option<Vec<(usize, usize)>> {
+        let piece = board.get(x, y);
+        board.lines_through(x, y, self.win_length).find(|line| self.is_winning_line(piece, line.len()))
+    }
+
+    // Check whether a run of line_len stones of the given color wins the game
+    pub fn is_winning_line(&self, piece: Piece, line_len: usize) -> bool {
+        if self.overline_wins && !(self.renju && piece == Piece::Black) {
+            line_len >= self.win_length
+        } else {
+            line_len == self.win_length
+        }
+    }
+
+    // Check whether the opening rule allows a stone on (x, y) on the given move
+    // number. The first player moves on turns 0, num_players, 2 * num_players...
+    pub fn opening_allows(&self, board: &Board, turn: usize, num_players: usize, x: usize, y: usize) -> bool {
+        let center = (board.rows / 2, board.cols / 2);
+        let is_center = (x, y) == center;
+        match self.opening {
+            Opening::Free => true,
+            Opening::Center => turn > 0 || is_center,
+            Opening::Tournament if turn == 0 => is_center,
+            Opening::Tournament if turn == num_players => x.abs_diff(center.0).max(y.abs_diff(center.1)) >= 3,
+            Opening::Tournament => true,
+        }
+    }
+
+    // Check whether the opening rule limits where the stone of the given move number may go
+    fn opening_restricts(&self, turn: usize, num_players: usize) -> bool {
+        match self.opening {
+            Opening::Free => false,
+            Opening::Center => turn == 0,
+            Opening::Tournament => turn == 0 || turn == num_players,
+        }
+    }
+
+    // Check whether placing a stone of the given color on the empty point (x, y)
+    // is a forbidden move
+    pub fn is_forbidden(&self, board: &Board, piece: Piece, x: usize, y: usize) -> bool {
+        self.renju && piece == Piece::Black && renju::is_forbidden(board, x, y, self.win_length)
+    }
+
+    // Stones the player to move still has to place once stones_played stones are
+    // on the board, in a turn that has just begun or is under way
+    pub fn stones_left_in_turn(&self, stones_played: usize) -> usize {
+        let mut turn_end = 0;
+        for turn in 0.. {
+            let stones = self.stones_per_turn.get(turn).or(self.stones_per_turn.last()).copied().unwrap_or(1);
+            turn_end += stones.max(1);
+            if turn_end > stones_played {
+                break;
+            }
+        }
+        turn_end - stones_played
+    }
+
+    pub fn is_move_limit_reached(&self, moves_played: usize) -> bool {
+        self.max_moves.is_some_and(|max| moves_played >= max)
+    }
+
+    // Shortest line that counts as a threat for quiet_moves_to_draw
+    pub fn threat_length(&self) -> usize {
+        self.win_length.saturating_sub(2).max(2)
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> RuleSet {
+        RuleSet::pente()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_move() {
+        let mut board = Board::new(3);
+        let rules = RuleSet::default();
+        assert_eq!(rules.apply_move(&mut board, 0, 0, Piece::Black), Ok(MoveResult::default()));
+        assert_eq!(rules.apply_move(&mut board, 0, 0, Piece::White), Err(MoveError::Occupied));
+        assert_eq!(rules.apply_move(&mut board, 3, 0, Piece::Black), Err(MoveError::OutOfBounds));
+    }
+
+    #[test]
+    fn test_winning_line_checks_every_direction() {
+        // A horizontal six and a vertical five through (7, 5). The horizontal
+        // line is looked at first.
+        let mut board = Board::new(15);
+        for col in 2..8 {
+            board.set(7, col, Piece::Black);
+        }
+        for row in [5, 6, 8, 9] {
+            board.set(row, 5, Piece::Black);
+        }
+        let five: Vec<_> = (5..10).map(|row| (row, 5)).collect();
+        let rules = RuleSet { overline_wins: false, ..RuleSet::gomoku() };
+        assert_eq!(rules.winning_line(&board, 7, 5), Some(five));
+        assert_eq!(RuleSet::gomoku().winning_line(&board, 7, 5).map(|line| line.len()), Some(6));
+
+        board.set(9, 5, Piece::Empty);
+        assert_eq!(rules.winning_line(&board, 7, 5), None);
+    }
+
+    #[test]
+    fn test_can_place() {
+        let mut board = Board::new(5);
+        board.set(2, 2, Piece::Black);
+        board.set(0, 0, Piece::White);
+        let rules = RuleSet::tournament_pente();
+        // No point of a 5x5 board is three intersections from the center
+        assert!(!rules.can_place(&board, 2, 2, Piece::Black));
+        assert!(rules.can_place(&board, 3, 2, Piece::White));
+        assert!(RuleSet::default().can_place(&board, 2, 2, Piece::Black));
+
+        let mut board = Board::new(2);
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            board.set(x, y, Piece::White);
+        }
+        assert!(!RuleSet::default().can_place(&board, 4, 2, Piece::Black));
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mut board = Board::new(9);
+        for col in 0..5 {
+            board.set(4, col, Piece::Black);
+        }
+        let pieces = [Piece::Black, Piece::White];
+        let mut position = Position {
+            board: &board,
+            pieces: &pieces,
+            captures: &[0, 10],
+            turn: 9,
+            quiet_moves: 0,
+            next_stone: Some(Piece::White),
+            previous_move: None,
+        };
+        let rules = RuleSet::default();
+        let outcome = rules.outcome(&position, (4, 2), 0);
+        assert_eq!(outcome.winner, Some(0));
+        assert_eq!(outcome.winning_line.map(|line| line.len()), Some(5));
+        assert_eq!(rules.outcome(&position, (0, 8), 0).winner, Some(1));
+
+        // Partners pool their captures and share the win
+        let pieces = [Piece::Black, Piece::White, Piece::Red, Piece::Green];
+        position.pieces = &pieces;
+        position.captures = &[4, 0, 6, 0];
+        let outcome = RuleSet::team_pente().outcome(&position, (0, 8), 0);
+        assert_eq!(outcome.winner, Some(0));
+        assert_eq!(outcome.rewards, vec![1.0, -1.0, 1.0, -1.0]);
+
+        position.captures = &[0; 4];
+        position.quiet_moves = 3;
+        let rules = RuleSet { quiet_moves_to_draw: Some(3), ..RuleSet::gomoku() };
+        assert_eq!(rules.outcome(&position, (0, 8), 1).draw_reason, Some(DrawReason::NoThreats));
+    }
+
+    #[test]
+    fn test_apply_move_captures() {
+        let mut board = Board::new(5);
+        let rules = RuleSet::default();
+        for (x, y, piece) in [(1, 1, Piece::Black), (1, 0, Piece::White), (1, 2, Piece::Black)] {
+            rules.apply_move(&mut board, x, y, piece).unwrap();
+        }
+
+        let result = rules.apply_move(&mut board, 1, 3, Piece::White).unwrap();
+        assert_eq!(result.captured.len(), 2);
+        assert_eq!(board.get(1, 1), Piece::Empty);
+        assert_eq!(board.get(1, 2), Piece::Empty);
+
+        // Nothing is captured in Gomoku, and forbidden points are refused in Renju
+        let mut board = Board::new(5);
+        for (x, y, piece) in [(1, 1, Piece::Black), (1, 0, Piece::White), (1, 2, Piece::Black)] {
+            RuleSet::gomoku().apply_move(&mut board, x, y, piece).unwrap();
+        }
+        assert!(RuleSet::gomoku().apply_move(&mut board, 1, 3, Piece::White).unwrap().captured.is_empty());
+        let mut board = Board::new(15);
+        for (x, y) in [(7, 5), (7, 6), (5, 8), (6, 8)] {
+            board.set(x, y, Piece::Black);
+        }
+        assert_eq!(RuleSet::renju().apply_move(&mut board, 7, 8, Piece::Black), Err(MoveError::Forbidden));
+        assert!(RuleSet::renju().apply_move(&mut board, 7, 8, Piece::White).is_ok());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::action::Action;
+use crate::board::Board;
+
+// One of the eight rotations and reflections of a board. Rotations are
+// clockwise; the transposes mirror the board across one of its diagonals.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipRows,
+    FlipCols,
+    Transpose,
+    AntiTranspose,
+}
+
+pub const SYMMETRIES: [Symmetry; 8] = [
+    Symmetry::Identity,
+    Symmetry::Rotate90,
+    Symmetry::Rotate180,
+    Symmetry::Rotate270,
+    Symmetry::FlipRows,
+    Symmetry::FlipCols,
+    Symmetry::Transpose,
+    Symmetry::AntiTranspose,
+];
+
+impl Symmetry {
+    // The symmetries that map the board onto itself: all eight on a square
+    // board, only those that keep rows and columns apart otherwise
+    pub fn of(board: &Board) -> Vec<Symmetry> {
+        SYMMETRIES.into_iter()
+            .filter(|s| board.rows == board.cols || !s.swaps_axes())
+            .collect()
+    }
+
+    // Whether rows become columns, which turns a rows x cols board into cols x rows
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Symmetry::Rotate90 | Symmetry::Rotate270 | Symmetry::Transpose | Symmetry::AntiTranspose)
+    }
+
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            s => s,
+        }
+    }
+
+    // Where (row, col) of the given board ends up
+    pub fn point(self, board: &Board, (row, col): (usize, usize)) -> (usize, usize) {
+        let (last_row, last_col) = (board.rows - 1, board.cols - 1);
+        match self {
+            Symmetry::Identity => (row, col),
+            Symmetry::Rotate90 => (col, last_row - row),
+            Symmetry::Rotate180 => (last_row - row, last_col - col),
+            Symmetry::Rotate270 => (last_col - col, row),
+            Symmetry::FlipRows => (last_row - row, col),
+            Symmetry::FlipCols => (row, last_col - col),
+            Symmetry::Transpose => (col, row),
+            Symmetry::AntiTranspose => (last_col - col, last_row - row),
+        }
+    }
+
+    // Choices aren't tied to a point, so only placements move
+    pub fn action(self, board: &Board, action: Action) -> Action {
+        match action {
+            Action::Place(row, col) => self.point(board, (row, col)).into(),
+            choice => choice,
+        }
+    }
+
+    // An empty board of the shape the given board takes after the transform
+    fn empty_board(self, board: &Board) -> Board {
+        if !self.swaps_axes() {
+            return board.cleared();
+        }
+        let mut transformed = Board::rectangular(board.cols, board.rows);
+        transformed.topology = board.topology;
+        transformed
+    }
+
+    // The board with every stone moved, keeping its topology
+    pub fn board(self, board: &Board) -> Board {
+        let mut transformed = self.empty_board(board);
+        for row in 0..board.rows {
+            for col in 0..board.cols {
+                if !board.is_empty(row, col) {
+                    let (r, c) = self.point(board, (row, col));
+                    transformed.set(r, c, board.get(row, col));
+                }
+            }
+        }
+        transformed
+    }
+
+    // Rearrange anything indexed by the flat action space of the given board,
+    // such as a policy or an action mask, to match the transformed board
+    pub fn policy<T: Copy>(self, board: &Board, policy: &[T]) -> Vec<T> {
+        assert_eq!(policy.len(), Action::space_size(board), "policy doesn't match the board");
+        let transformed = self.empty_board(board);
+        let mut out = policy.to_vec();
+        for (index, &value) in policy.iter().enumerate() {
+            let action = Action::from_index(index, board).unwrap();
+            out[self.action(board, action).index(&transformed)] = value;
+        }
+        out
+    }
+}
+
+// The symmetric copy of the board with the smallest Zobrist hash, along with the
+// symmetry that produces it. Boards that are rotations or reflections of each
+// other share the same canonical form.
+pub fn canonical(board: &Board) -> (Board, Symmetry) {
+    Symmetry::of(board).into_iter()
+        .map(|s| (s.board(board), s))
+        .min_by_key(|(transformed, _)| transformed.hash())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Piece;
+
+    #[test]
+    fn test_symmetries_round_trip() {
+        let mut board = Board::new(5);
+        board.set(0, 1, Piece::Black);
+        board.set(2, 4, Piece::White);
+        assert_eq!(Symmetry::Rotate90.board(&board).get(1, 4), Piece::Black);
+        assert_eq!(Symmetry::AntiTranspose.point(&board, (0, 1)), (3, 4));
+
+        let mut hashes: Vec<_> = SYMMETRIES.iter().map(|s| s.board(&board).hash()).collect();
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), 8);
+        for s in SYMMETRIES {
+            let transformed = s.board(&board);
+            assert_eq!(s.inverse().board(&transformed), board);
+            assert_eq!(canonical(&transformed).0, canonical(&board).0);
+        }
+
+        // Only the four symmetries that keep the shape apply to a rectangle
+        let board = Board::toroidal(3, 5);
+        assert_eq!(Symmetry::of(&board).len(), 4);
+        assert_eq!(Symmetry::Transpose.board(&board).rows, 5);
+        assert_eq!(Symmetry::Transpose.board(&board).topology, board.topology);
+    }
+
+    #[test]
+    fn test_policy_follows_moves() {
+        let board = Board::rectangular(3, 4);
+        let policy: Vec<_> = (0..Action::space_size(&board)).collect();
+        for s in SYMMETRIES {
+            let transformed = s.board(&board);
+            let moved = s.policy(&board, &policy);
+            for index in policy.iter().copied() {
+                let action = Action::from_index(index, &board).unwrap();
+                assert_eq!(moved[s.action(&board, action).index(&transformed)], index);
+            }
+        }
+        let moved = Symmetry::FlipCols.policy(&board, &policy);
+        assert_eq!(moved[0], 3);
+        assert_eq!(moved[12..], policy[12..]);
+    }
+}
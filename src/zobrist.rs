@@ -0,0 +1,50 @@
+use crate::board::Piece;
+use crate::game::Phase;
+
+// Keys for Zobrist hashing. They are derived from fixed tags rather than a
+// random table, so a position hashes the same in every run and on every machine,
+// and boards of any size need no precomputed storage.
+
+const STONE: u64 = 1;
+const SIDE_TO_MOVE: u64 = 2;
+const CAPTURES: u64 = 3;
+const PHASE: u64 = 4;
+
+// SplitMix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn key(tag: u64, a: u64, b: u64, c: u64) -> u64 {
+    mix(mix(mix(mix(tag) ^ a) ^ b) ^ c)
+}
+
+// Key of a stone of the given color on (row, col)
+pub fn stone(piece: Piece, row: usize, col: usize) -> u64 {
+    key(STONE, piece as u64, row as u64, col as u64)
+}
+
+// Key of the color whose turn it is
+pub fn side_to_move(piece: Piece) -> u64 {
+    key(SIDE_TO_MOVE, piece as u64, 0, 0)
+}
+
+// Key of a color having captured the given number of stones
+pub fn captures(piece: Piece, count: usize) -> u64 {
+    key(CAPTURES, piece as u64, count as u64, 0)
+}
+
+// Key of an opening phase. Normal play has none, so positions reached through
+// different openings hash alike once colors are settled.
+pub fn phase(phase: Phase) -> u64 {
+    match phase {
+        Phase::Play => 0,
+        Phase::Placing { until } => key(PHASE, 1, until as u64, 0),
+        Phase::SwapChoice => key(PHASE, 2, 0, 0),
+        Phase::Swap2Choice => key(PHASE, 3, 0, 0),
+        Phase::ColorChoice => key(PHASE, 4, 0, 0),
+    }
+}